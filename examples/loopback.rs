@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MIT
+
+//! Vendor-defined device that echoes output reports back as input reports.
+//!
+//! Every second it also sends an input report with a counter, and it keeps a feature report that
+//! the host can read and write. Try it with the hidraw node of the device, for example with
+//! `hid-tools` or by writing to `/dev/hidrawN` directly. Needs access to `/dev/uhid`:
+//!
+//! ```text
+//! sudo cargo run --example loopback
+//! ```
+
+use std::time::{Duration, Instant};
+
+use uhid_rs::descriptor::{Collection, ReportDescriptor, DATA_VAR_ABS};
+use uhid_rs::usages::Usage;
+use uhid_rs::{DeviceBuilder, Event, ReportType, UhidError};
+
+const REPORT_LEN: usize = 8;
+
+fn descriptor() -> Vec<u8> {
+    ReportDescriptor::new()
+        .usage_page(0xff00u16)
+        .usage(Usage(0x01))
+        .collection(Collection::Application)
+        .logical_minimum(0)
+        .logical_maximum(255)
+        .report_size(8)
+        .report_count(REPORT_LEN as u32)
+        .usage(Usage(0x02))
+        .input(DATA_VAR_ABS)
+        .usage(Usage(0x03))
+        .output(DATA_VAR_ABS)
+        .usage(Usage(0x04))
+        .feature(DATA_VAR_ABS)
+        .end_collection()
+        .build()
+}
+
+fn main() -> Result<(), UhidError> {
+    let mut dev = DeviceBuilder::new()
+        .name("uhid-rs loopback")
+        .rdesc(&descriptor())
+        .vendor(0x1234)
+        .product(0x5678)
+        .build()?;
+    println!("created {}", dev);
+
+    let mut feature = [0u8; REPORT_LEN];
+    let mut counter: u8 = 0;
+    let mut next_tick = Instant::now();
+
+    loop {
+        let now = Instant::now();
+        if now >= next_tick {
+            let mut report = [0u8; REPORT_LEN];
+            report[0] = counter;
+            dev.input(&report)?;
+            counter = counter.wrapping_add(1);
+            next_tick = now + Duration::from_secs(1);
+        }
+
+        let event = match dev.wait_for(|_| true, next_tick.saturating_duration_since(Instant::now())) {
+            Ok(event) => event,
+            Err(UhidError::Timeout) => continue,
+            Err(e) => return Err(e),
+        };
+
+        match event {
+            Event::Output { data, .. } => {
+                /* echo it back inverted, so it's easy to tell apart from the output */
+                let echo: Vec<u8> = data.iter().map(|byte| !byte).collect();
+                println!("output {:02x?}, echoing {:02x?}", data, echo);
+                dev.input(&echo)?;
+            }
+            Event::GetReport { id, rtype: ReportType::Feature, .. } => {
+                println!("get feature {:02x?}", feature);
+                dev.get_report_reply(id, 0, &feature)?;
+            }
+            Event::SetReport { id, rtype: ReportType::Feature, data, .. } => {
+                println!("set feature {:02x?}", data);
+                let len = data.len().min(REPORT_LEN);
+                feature[..len].copy_from_slice(&data[..len]);
+                dev.set_report_reply(id, 0)?;
+            }
+            Event::GetReport { id, .. } => dev.get_report_reply(id, libc::EIO as u16, &[])?,
+            Event::SetReport { id, .. } => dev.set_report_reply(id, libc::EIO as u16)?,
+            Event::Stop => {
+                println!("stopped");
+                return dev.destroy();
+            }
+            event => println!("{:?}", event),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+// SPDX-License-Identifier: MIT
+
+use crate::{Bus, CreationMode, Device, DeviceConfig, DeviceId, UhidError, MAX_COUNTRY};
+
+/// Builder for a [`DeviceConfig`], or directly for a created [`Device`].
+#[derive(Clone, Debug, Default)]
+pub struct DeviceBuilder {
+    config: DeviceConfig,
+    nonblocking: bool,
+}
+
+impl DeviceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.config.name = name.to_string();
+        self
+    }
+
+    /// Sets the physical location, conventionally the address of the host controller.
+    pub fn phys(mut self, phys: &str) -> Self {
+        self.config.phys = phys.to_string();
+        self
+    }
+
+    /// Sets the physical location to a USB-style path, `usb-{controller}-{port}/input{interface}`,
+    /// such as `usb-0000:00:14.0-1/input0` for the first interface of the device on port 1 of the
+    /// controller at PCI address `0000:00:14.0`.
+    ///
+    /// libinput groups devices with the same bus, vendor and product whose paths match up to the
+    /// last `/`, which is how a touchpad and its buttons, or a tablet and its pad, are handled as
+    /// one device. Give the devices of an emulated composite device the same `controller` and
+    /// `port`, and each its own `interface`. Fails with [`UhidError::StringTooLong`] if the path
+    /// doesn't fit in the 63 bytes of `phys`.
+    pub fn phys_path(mut self, controller: &str, port: &str, interface: u8) -> Result<Self, UhidError> {
+        let phys = format!("usb-{}-{}/input{}", controller, port, interface);
+        crate::str_to_fixed::<64>("phys", &phys)?;
+        self.config.phys = phys;
+        Ok(self)
+    }
+
+    /// Sets the serial number, stored in the `uniq` field.
+    ///
+    /// This is what evdev (`EVIOCGUNIQ`), hidraw (`HIDIOCGRAWUNIQ`) and udev (`HID_UNIQ`) report,
+    /// and what most host software uses. Vendor tools that instead ask for the serial with a
+    /// feature request can be served with [`Device::set_serial_feature`]. At most 63 bytes.
+    pub fn serial(mut self, serial: &str) -> Self {
+        self.config.uniq = serial.to_string();
+        self
+    }
+
+    pub fn rdesc(mut self, rdesc: &[u8]) -> Self {
+        self.config.rdesc = rdesc.to_vec();
+        self
+    }
+
+    pub fn bus(mut self, bus: Bus) -> Self {
+        self.config.bus = bus;
+        self
+    }
+
+    pub fn vendor(mut self, vendor: u32) -> Self {
+        self.config.vendor = vendor;
+        self
+    }
+
+    pub fn product(mut self, product: u32) -> Self {
+        self.config.product = product;
+        self
+    }
+
+    /// Sets both the vendor and product ID.
+    pub fn id(mut self, id: DeviceId) -> Self {
+        self.config.vendor = id.vendor as u32;
+        self.config.product = id.product as u32;
+        self
+    }
+
+    /// Sets the device version, fixed once the device is created, see [`Device::version`].
+    pub fn version(mut self, version: u32) -> Self {
+        self.config.version = version;
+        self
+    }
+
+    /// Sets up a Bluetooth device with the address `address`, given as `AA:BB:CC:DD:EE:FF`.
+    ///
+    /// Like the kernel's Bluetooth HID transports, this stores the device address in `uniq` in
+    /// lowercase and switches the bus to [`Bus::BLUETOOTH`]. The kernel puts the address of the
+    /// local adapter in `phys`, set it with [`phys`](Self::phys) to mimic that too.
+    pub fn bluetooth_address(mut self, address: &str) -> Result<Self, UhidError> {
+        let groups: Vec<&str> = address.split(':').collect();
+        let valid = groups.len() == 6
+            && groups.iter().all(|group| group.len() == 2 && group.chars().all(|c| c.is_ascii_hexdigit()));
+        if !valid {
+            return Err(UhidError::InvalidAddress(address.to_string()));
+        }
+
+        self.config.uniq = address.to_ascii_lowercase();
+        self.config.bus = Bus::BLUETOOTH;
+        Ok(self)
+    }
+
+    /// Sets the HID country code, which must be in the 0–35 range defined by the HID specification.
+    pub fn country(mut self, country: u32) -> Result<Self, UhidError> {
+        if country > MAX_COUNTRY {
+            return Err(UhidError::InvalidCountry(country));
+        }
+        self.config.country = country;
+        Ok(self)
+    }
+
+    /// Reserved for creation flags of future kernels.
+    ///
+    /// This is an advanced option and depends on the kernel version. No kernel so far has a flags
+    /// field in `struct uhid_create2_req`, so creating a device with non-zero flags fails with
+    /// [`UhidError::UnsupportedFlags`] rather than silently dropping them.
+    pub fn raw_flags(mut self, flags: u64) -> Self {
+        self.config.raw_flags = flags;
+        self
+    }
+
+    /// Selects the event used to create the device, [`CreationMode::Create2`] by default.
+    ///
+    /// | Kernel      | Create2 | Legacy |
+    /// |-------------|---------|--------|
+    /// | 3.6 – 3.17  | no      | yes    |
+    /// | 3.18 and up | yes     | yes    |
+    ///
+    /// Devices created with the legacy `UHID_CREATE` event also send their input reports with the
+    /// legacy `UHID_INPUT` event, as older kernels don't know `UHID_INPUT2`. Those kernels also
+    /// have no SetReport requests, and a smaller `struct uhid_event`: a device created with the
+    /// legacy event takes each read as a whole event once it holds the event's fields, see
+    /// [`event_buffer_size`](crate::event_buffer_size). [`CreationMode::Auto`] tries Create2
+    /// first and falls back to the legacy events when the kernel refuses it with `EOPNOTSUPP` or
+    /// `EINVAL`.
+    pub fn creation_mode(mut self, mode: CreationMode) -> Self {
+        self.config.creation_mode = mode;
+        self
+    }
+
+    /// Makes [`build`](Self::build) open `/dev/uhid` with `O_NONBLOCK`, see
+    /// [`Device::new_nonblocking`].
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    pub fn config(self) -> DeviceConfig {
+        self.config
+    }
+
+    /// Opens `/dev/uhid` and creates the device.
+    ///
+    /// Like [`Device::create_with`], this returns before the kernel brought the device up.
+    pub fn build(self) -> Result<Device, UhidError> {
+        let mut dev = if self.nonblocking {
+            Device::new_nonblocking()?
+        } else {
+            Device::new()?
+        };
+        dev.create_with(&self.config)?;
+        Ok(dev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTransport;
+
+    #[test]
+    fn config() {
+        let config = DeviceBuilder::new()
+            .name("built")
+            .phys("usb-0000:00:14.0-1/input0")
+            .serial("SN-0042")
+            .rdesc(&[0x05, 0x01])
+            .bus(Bus::VIRTUAL)
+            .vendor(0x1234)
+            .product(0x4321)
+            .version(0x0100)
+            .country(33)
+            .unwrap()
+            .config();
+
+        assert_eq!(config.name, "built");
+        assert_eq!(config.phys, "usb-0000:00:14.0-1/input0");
+        assert_eq!(config.uniq, "SN-0042");
+        assert_eq!(config.rdesc, [0x05, 0x01]);
+        assert_eq!(config.bus, Bus::VIRTUAL);
+        assert_eq!(config.vendor, 0x1234);
+        assert_eq!(config.product, 0x4321);
+        assert_eq!(config.version, 0x0100);
+        assert_eq!(config.country, 33);
+        assert_eq!(config.raw_flags, 0);
+    }
+
+    #[test]
+    fn phys_path() {
+        let config = DeviceBuilder::new().phys_path("0000:00:14.0", "1.2", 1).unwrap().config();
+        assert_eq!(config.phys, "usb-0000:00:14.0-1.2/input1");
+
+        /* "usb-" and "-" and "/input255" leave 49 bytes for the controller and port */
+        let controller = "c".repeat(40);
+        assert_eq!(DeviceBuilder::new().phys_path(&controller, "123456789", 255).unwrap().config().phys.len(), 63);
+        match DeviceBuilder::new().phys_path(&controller, "1234567890", 255) {
+            Err(UhidError::StringTooLong { field: "phys", len: 64, max: 63 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn id() {
+        let config = DeviceBuilder::new().id("046d:c52b".parse().unwrap()).config();
+        assert_eq!((config.vendor, config.product), (0x046d, 0xc52b));
+    }
+
+    #[test]
+    fn country() {
+        assert_eq!(DeviceBuilder::new().country(35).unwrap().config().country, 35);
+        match DeviceBuilder::new().country(36) {
+            Err(UhidError::InvalidCountry(country)) => assert_eq!(country, 36),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let mut dev = Device::with_transport(MockTransport::new());
+        let config = DeviceConfig { country: 36, ..DeviceConfig::default() };
+        assert!(dev.create_with(&config).is_err());
+    }
+
+    #[test]
+    fn raw_flags() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        let config = DeviceBuilder::new().name("flags").rdesc(&[0x05, 0x01]).raw_flags(1 << 3).config();
+
+        match dev.create_with(&config) {
+            Err(UhidError::UnsupportedFlags(flags)) => assert_eq!(flags, 1 << 3),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(mock.written().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "root-tests")]
+    fn nonblocking() {
+        let builder = DeviceBuilder::new().name("nonblocking").rdesc(&crate::presets::boot_mouse());
+        assert!(builder.clone().nonblocking(true).build().unwrap().is_nonblocking().unwrap());
+        assert!(!builder.build().unwrap().is_nonblocking().unwrap());
+    }
+
+    #[test]
+    fn bluetooth_address() {
+        let config = DeviceBuilder::new().bluetooth_address("AA:bb:0C:dd:EE:0f").unwrap().config();
+        assert_eq!(config.uniq, "aa:bb:0c:dd:ee:0f");
+        assert_eq!(config.bus, Bus::BLUETOOTH);
+
+        let invalid = [
+            "",
+            "aa:bb:cc:dd:ee",
+            "aa:bb:cc:dd:ee:ff:00",
+            "aa-bb-cc-dd-ee-ff",
+            "a:bb:cc:dd:ee:fff",
+            "gg:bb:cc:dd:ee:ff",
+        ];
+        for address in &invalid {
+            match DeviceBuilder::new().bluetooth_address(address) {
+                Err(UhidError::InvalidAddress(a)) => assert_eq!(&a, address),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+}
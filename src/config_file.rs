@@ -0,0 +1,235 @@
+// SPDX-License-Identifier: MIT
+
+//! Device definition files, see [`DeviceConfig::save`].
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::{Bus, CreationMode, DeviceConfig, UhidError};
+
+const CREATION_MODES: [(CreationMode, &str); 3] =
+    [(CreationMode::Create2, "create2"), (CreationMode::Legacy, "legacy"), (CreationMode::Auto, "auto")];
+
+fn invalid(line: usize, reason: &str) -> UhidError {
+    UhidError::InvalidConfig { line, reason: reason.to_string() }
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => write!(quoted, "\\u{:04X}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn unquote(value: &str, line: usize) -> Result<String, UhidError> {
+    let inner = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => inner,
+        None => return Err(invalid(line, "expected a quoted string")),
+    };
+
+    let mut s = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            s.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => s.push('"'),
+            Some('\\') => s.push('\\'),
+            Some('n') => s.push('\n'),
+            Some('t') => s.push('\t'),
+            Some('r') => s.push('\r'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    Some(c) if hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) => s.push(c),
+                    _ => return Err(invalid(line, "invalid \\u escape")),
+                }
+            }
+            _ => return Err(invalid(line, "invalid escape")),
+        }
+    }
+    Ok(s)
+}
+
+fn integer(value: &str, line: usize) -> Result<u64, UhidError> {
+    let (digits, radix) = match value.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (value, 10),
+    };
+    /* from_str_radix takes a sign, TOML integers here don't */
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(invalid(line, "expected an integer"));
+    }
+    u64::from_str_radix(digits, radix).map_err(|_| invalid(line, "integer too large"))
+}
+
+fn u32_value(value: &str, line: usize) -> Result<u32, UhidError> {
+    match integer(value, line)? {
+        n if n <= u32::MAX as u64 => Ok(n as u32),
+        _ => Err(invalid(line, "integer too large")),
+    }
+}
+
+fn hex_bytes(hex: &str, line: usize) -> Result<Vec<u8>, UhidError> {
+    let hex: Vec<char> = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if hex.len() % 2 == 1 || !hex.iter().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid(line, "expected hex bytes"));
+    }
+    Ok(hex.chunks(2).map(|pair| (pair[0].to_digit(16).unwrap() * 16 + pair[1].to_digit(16).unwrap()) as u8).collect())
+}
+
+pub(crate) fn to_string(config: &DeviceConfig) -> String {
+    let mut s = String::new();
+    writeln!(s, "name = {}", quote(&config.name)).unwrap();
+    writeln!(s, "phys = {}", quote(&config.phys)).unwrap();
+    writeln!(s, "uniq = {}", quote(&config.uniq)).unwrap();
+    writeln!(s, "bus = {}", quote(&config.bus.to_string())).unwrap();
+    writeln!(s, "vendor = 0x{:04x}", config.vendor).unwrap();
+    writeln!(s, "product = 0x{:04x}", config.product).unwrap();
+    writeln!(s, "version = 0x{:04x}", config.version).unwrap();
+    writeln!(s, "country = {}", config.country).unwrap();
+    writeln!(s, "raw_flags = 0x{:x}", config.raw_flags).unwrap();
+    let mode = CREATION_MODES.iter().find(|(mode, _)| *mode == config.creation_mode).unwrap().1;
+    writeln!(s, "creation_mode = {}", quote(mode)).unwrap();
+    let rdesc: String = config.rdesc.iter().map(|byte| format!("{:02x}", byte)).collect();
+    writeln!(s, "rdesc = \"{}\"", rdesc).unwrap();
+    s
+}
+
+pub(crate) fn from_str(text: &str) -> Result<DeviceConfig, UhidError> {
+    let mut config = DeviceConfig::default();
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = raw_line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match trimmed.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => return Err(invalid(line, "expected `key = value`")),
+        };
+
+        match key {
+            "name" => config.name = unquote(value, line)?,
+            "phys" => config.phys = unquote(value, line)?,
+            "uniq" => config.uniq = unquote(value, line)?,
+            "bus" => config.bus = unquote(value, line)?.parse::<Bus>().map_err(|_| invalid(line, "unknown bus"))?,
+            "vendor" => config.vendor = u32_value(value, line)?,
+            "product" => config.product = u32_value(value, line)?,
+            "version" => config.version = u32_value(value, line)?,
+            "country" => config.country = u32_value(value, line)?,
+            "raw_flags" => config.raw_flags = integer(value, line)?,
+            "creation_mode" => {
+                let name = unquote(value, line)?;
+                config.creation_mode = match CREATION_MODES.iter().find(|(_, n)| *n == name) {
+                    Some(&(mode, _)) => mode,
+                    None => return Err(invalid(line, "unknown creation mode")),
+                };
+            }
+            "rdesc" => config.rdesc = hex_bytes(&unquote(value, line)?, line)?,
+            _ => return Err(invalid(line, &format!("unknown key `{}`", key))),
+        }
+    }
+    Ok(config)
+}
+
+impl DeviceConfig {
+    /// Writes the configuration to `path` as a device definition file.
+    ///
+    /// The format is a small subset of TOML: one `key = value` line per field, strings quoted,
+    /// IDs in hex, and the report descriptor as a string of hex bytes. Whitespace in the
+    /// descriptor string and `#` comment lines are allowed when loading, so definitions can be
+    /// written by hand.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), UhidError> {
+        fs::write(path, to_string(self)).map_err(UhidError::ConfigFile)
+    }
+
+    /// Reads a device definition written by [`save`](Self::save).
+    ///
+    /// Missing keys keep their default value, unknown keys are an error.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, UhidError> {
+        from_str(&fs::read_to_string(path).map_err(UhidError::ConfigFile)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+
+    fn config() -> DeviceConfig {
+        DeviceConfig {
+            name: "Test \"quoted\" \\ mouse\u{1}".to_string(),
+            phys: "usb-0000:00:14.0-1/input0".to_string(),
+            uniq: "SN-0042".to_string(),
+            rdesc: presets::boot_mouse(),
+            bus: Bus::BLUETOOTH,
+            vendor: 0x046d,
+            product: 0xc077,
+            version: 0x0111,
+            country: 33,
+            raw_flags: 0,
+            creation_mode: CreationMode::Auto,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = config();
+        assert_eq!(from_str(&to_string(&config)).unwrap(), config);
+
+        let path = std::env::temp_dir().join(format!("uhid-rs-config-{}.toml", std::process::id()));
+        config.save(&path).unwrap();
+        assert_eq!(DeviceConfig::load(&path).unwrap(), config);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn hand_written() {
+        let text = "# mouse\nname = \"Mouse\"\n\nvendor = 0x1234\nproduct = 17\nrdesc = \"05 01 09 02\"\n";
+        let config = from_str(text).unwrap();
+        assert_eq!(config.name, "Mouse");
+        assert_eq!((config.vendor, config.product), (0x1234, 17));
+        assert_eq!(config.rdesc, [0x05, 0x01, 0x09, 0x02]);
+        assert_eq!(config.bus, Bus::default());
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = [
+            ("name = Mouse", 1),
+            ("\nvendor = -1", 2),
+            ("vendor = 0x100000000", 1),
+            ("rdesc = \"051\"", 1),
+            ("bus = \"can\"", 1),
+            ("creation_mode = \"create3\"", 1),
+            ("colour = \"red\"", 1),
+            ("name", 1),
+            ("name = \"\\q\"", 1),
+        ];
+        for (text, expected) in &cases {
+            match from_str(text) {
+                Err(UhidError::InvalidConfig { line, .. }) => assert_eq!(line, *expected, "{}", text),
+                other => panic!("unexpected result for {}: {:?}", text, other),
+            }
+        }
+
+        match DeviceConfig::load("/nonexistent/uhid-rs.toml") {
+            Err(UhidError::ConfigFile(e)) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,1023 @@
+// SPDX-License-Identifier: MIT
+
+//! Report descriptor parsing and building.
+
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use crate::usages::{consumer, generic_desktop, Usage, UsagePage};
+use crate::{Bus, Device, DeviceConfig, ReportType, UhidError};
+
+/// Size of a single report, as declared by the report descriptor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReportInfo {
+    pub report_id: Option<u8>,
+    pub kind: ReportType,
+    /// Length of the report data in bits, not counting the report ID.
+    pub bit_length: usize,
+}
+
+impl ReportInfo {
+    /// Length of the report on the wire in bytes, including the report ID prefix if there is one.
+    pub fn byte_length(&self) -> usize {
+        self.bit_length.div_ceil(8) + self.report_id.is_some() as usize
+    }
+}
+
+/// Reports declared by a report descriptor, in order of first appearance.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DescriptorInfo {
+    pub reports: Vec<ReportInfo>,
+    /// Usages of the top-level collections, with the usage page in the high 16 bits.
+    pub applications: Vec<u32>,
+}
+
+impl DescriptorInfo {
+    pub fn report(&self, report_id: Option<u8>, kind: ReportType) -> Option<&ReportInfo> {
+        self.reports.iter().find(|r| r.report_id == report_id && r.kind == kind)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct GlobalState {
+    usage_page: u32,
+    report_size: u32,
+    report_count: u32,
+    report_id: Option<u8>,
+}
+
+const TYPE_MAIN: u8 = 0;
+const TYPE_GLOBAL: u8 = 1;
+const TYPE_LOCAL: u8 = 2;
+
+const MAIN_INPUT: u8 = 0x8;
+const MAIN_OUTPUT: u8 = 0x9;
+const MAIN_FEATURE: u8 = 0xb;
+const MAIN_COLLECTION: u8 = 0xa;
+const MAIN_END_COLLECTION: u8 = 0xc;
+
+const GLOBAL_USAGE_PAGE: u8 = 0x0;
+const GLOBAL_LOGICAL_MINIMUM: u8 = 0x1;
+const GLOBAL_LOGICAL_MAXIMUM: u8 = 0x2;
+const GLOBAL_PHYSICAL_MINIMUM: u8 = 0x3;
+const GLOBAL_PHYSICAL_MAXIMUM: u8 = 0x4;
+const GLOBAL_UNIT_EXPONENT: u8 = 0x5;
+const GLOBAL_UNIT: u8 = 0x6;
+const GLOBAL_REPORT_SIZE: u8 = 0x7;
+const GLOBAL_REPORT_ID: u8 = 0x8;
+const GLOBAL_REPORT_COUNT: u8 = 0x9;
+const GLOBAL_PUSH: u8 = 0xa;
+const GLOBAL_POP: u8 = 0xb;
+
+const LOCAL_USAGE: u8 = 0x0;
+const LOCAL_USAGE_MINIMUM: u8 = 0x1;
+const LOCAL_USAGE_MAXIMUM: u8 = 0x2;
+
+const LONG_ITEM_PREFIX: u8 = 0xfe;
+
+fn invalid(offset: usize, reason: &str) -> UhidError {
+    UhidError::InvalidDescriptor(format!("{} at offset {}", reason, offset))
+}
+
+/// Works out the length of every input, output and feature report in `rdesc`.
+pub fn parse_descriptor(rdesc: &[u8]) -> Result<DescriptorInfo, UhidError> {
+    let mut info = DescriptorInfo::default();
+    let mut state = GlobalState::default();
+    let mut stack = Vec::new();
+    let mut offset = 0;
+    /* first usage since the last main item, what a collection is about */
+    let mut usage = None;
+    let mut depth = 0usize;
+
+    while offset < rdesc.len() {
+        let prefix = rdesc[offset];
+
+        if prefix == LONG_ITEM_PREFIX {
+            /* long items have no defined meaning, skip over them */
+            let size = match rdesc.get(offset + 1) {
+                Some(&size) => size as usize,
+                None => return Err(invalid(offset, "truncated long item")),
+            };
+            offset += 3 + size;
+            if offset > rdesc.len() {
+                return Err(invalid(offset, "truncated long item"));
+            }
+            continue;
+        }
+
+        let size = match prefix & 0x3 {
+            3 => 4,
+            size => size as usize,
+        };
+        let data = match rdesc.get(offset + 1..offset + 1 + size) {
+            Some(data) => data,
+            None => return Err(invalid(offset, "truncated item")),
+        };
+        let value = data.iter().rev().fold(0u32, |value, &byte| value << 8 | byte as u32);
+
+        match ((prefix >> 2) & 0x3, prefix >> 4) {
+            (TYPE_MAIN, tag @ (MAIN_INPUT | MAIN_OUTPUT | MAIN_FEATURE)) => {
+                let kind = match tag {
+                    MAIN_INPUT => ReportType::Input,
+                    MAIN_OUTPUT => ReportType::Output,
+                    _ => ReportType::Feature,
+                };
+                let bits = match state.report_size.checked_mul(state.report_count) {
+                    Some(bits) => bits as usize,
+                    None => return Err(invalid(offset, "report size overflow")),
+                };
+                let position = info
+                    .reports
+                    .iter()
+                    .position(|r| r.report_id == state.report_id && r.kind == kind);
+                let report = match position {
+                    Some(position) => &mut info.reports[position],
+                    None => {
+                        info.reports.push(ReportInfo {
+                            report_id: state.report_id,
+                            kind,
+                            bit_length: 0,
+                        });
+                        info.reports.last_mut().unwrap()
+                    }
+                };
+                report.bit_length = match report.bit_length.checked_add(bits) {
+                    Some(bit_length) => bit_length,
+                    None => return Err(invalid(offset, "report size overflow")),
+                };
+            }
+            (TYPE_MAIN, MAIN_COLLECTION) => {
+                if depth == 0 {
+                    info.applications.push(usage.unwrap_or(0));
+                }
+                depth += 1;
+            }
+            (TYPE_MAIN, MAIN_END_COLLECTION) => {
+                depth = depth.saturating_sub(1);
+            }
+            (TYPE_GLOBAL, GLOBAL_USAGE_PAGE) => state.usage_page = value,
+            (TYPE_GLOBAL, GLOBAL_REPORT_SIZE) => state.report_size = value,
+            (TYPE_GLOBAL, GLOBAL_REPORT_COUNT) => state.report_count = value,
+            (TYPE_GLOBAL, GLOBAL_REPORT_ID) => {
+                if value == 0 || value > 0xff {
+                    return Err(invalid(offset, "invalid report ID"));
+                }
+                state.report_id = Some(value as u8);
+            }
+            (TYPE_GLOBAL, GLOBAL_PUSH) => stack.push(state),
+            (TYPE_GLOBAL, GLOBAL_POP) => {
+                state = match stack.pop() {
+                    Some(state) => state,
+                    None => return Err(invalid(offset, "pop without push")),
+                };
+            }
+            (TYPE_LOCAL, LOCAL_USAGE) if usage.is_none() => {
+                /* 4-byte usages carry their own page */
+                usage = Some(if size == 4 { value } else { state.usage_page << 16 | value });
+            }
+            _ => (),
+        }
+        if (prefix >> 2) & 0x3 == TYPE_MAIN {
+            usage = None;
+        }
+
+        offset += 1 + size;
+    }
+
+    Ok(info)
+}
+
+/// Kind of device a report descriptor presents as, see [`descriptor_class`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceClass {
+    /// A Mouse or Pointer application collection.
+    Mouse,
+    /// A Keyboard or Keypad application collection.
+    Keyboard,
+    /// A Joystick or Gamepad application collection.
+    Gamepad,
+    /// A Consumer Control application collection, for media keys.
+    Consumer,
+    /// Anything else, including descriptors without a collection.
+    Generic,
+    /// Top-level collections of different classes, such as a keyboard with media keys.
+    Composite,
+}
+
+fn application_class(usage: u32) -> DeviceClass {
+    match (UsagePage((usage >> 16) as u16), Usage(usage as u16)) {
+        (UsagePage::GENERIC_DESKTOP, generic_desktop::POINTER | generic_desktop::MOUSE) => DeviceClass::Mouse,
+        (UsagePage::GENERIC_DESKTOP, generic_desktop::KEYBOARD | generic_desktop::KEYPAD) => DeviceClass::Keyboard,
+        (UsagePage::GENERIC_DESKTOP, generic_desktop::JOYSTICK | generic_desktop::GAMEPAD) => DeviceClass::Gamepad,
+        (UsagePage::CONSUMER, consumer::CONSUMER_CONTROL) => DeviceClass::Consumer,
+        _ => DeviceClass::Generic,
+    }
+}
+
+/// Classifies `rdesc` by the usages of its top-level collections.
+///
+/// This looks at the same usages the kernel and userspace use to pick a driver and a device type,
+/// but it is only a hint: a mouse collection without X and Y inputs is still reported as a mouse.
+/// Fails like [`parse_descriptor`] on a malformed descriptor.
+pub fn descriptor_class(rdesc: &[u8]) -> Result<DeviceClass, UhidError> {
+    let mut classes = parse_descriptor(rdesc)?.applications.into_iter().map(application_class);
+    let first = match classes.next() {
+        Some(class) => class,
+        None => return Ok(DeviceClass::Generic),
+    };
+    if classes.all(|class| class == first) {
+        Ok(first)
+    } else {
+        Ok(DeviceClass::Composite)
+    }
+}
+
+/// Checks that `report` has the length `info` declares for the input report `report_id`.
+///
+/// `report` is the report as sent, so for numbered reports it includes the report ID byte.
+pub fn check_report(info: &DescriptorInfo, report_id: Option<u8>, report: &[u8]) -> Result<(), UhidError> {
+    let expected = match info.report(report_id, ReportType::Input) {
+        Some(input) => input.byte_length(),
+        None => return Err(UhidError::UnknownReport(report_id)),
+    };
+    if report.len() != expected {
+        return Err(UhidError::ReportLengthMismatch { expected, got: report.len() });
+    }
+    Ok(())
+}
+
+/// Checks that the kernel accepts `rdesc` by creating a throwaway device with it on
+/// [`Bus::VIRTUAL`] and destroying it again.
+///
+/// This catches what [`parse_descriptor`] lets through but the kernel's parser or the generic
+/// HID driver refuses. A descriptor the kernel can't parse never gets a driver, so no Start
+/// arrives: if none does within `timeout`, or the driver stops the device right away, this fails
+/// with [`UhidError::CreateRejected`]. Needs access to `/dev/uhid`.
+pub fn validate_descriptor_live(rdesc: &[u8], timeout: Duration) -> Result<(), UhidError> {
+    let mut dev = Device::new()?;
+    let config = DeviceConfig {
+        name: "uhid-rs descriptor check".to_string(),
+        rdesc: rdesc.to_vec(),
+        bus: Bus::VIRTUAL,
+        ..DeviceConfig::default()
+    };
+    match dev.create_confirmed(&config, timeout) {
+        Ok(()) => dev.destroy(),
+        Err(UhidError::Timeout) => {
+            /* best effort, the device may never have been added */
+            let _ = dev.destroy();
+            Err(UhidError::CreateRejected)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether `line` is the `BUS:DEV:IF:DESCRIPTOR  <timestamp>` header `usbhid-dump` puts before
+/// each descriptor.
+fn is_dump_header(line: &str) -> bool {
+    let address = line.split_whitespace().next().unwrap_or("");
+    let fields: Vec<&str> = address.split(':').collect();
+    matches!(fields[..], [bus, dev, interface, "DESCRIPTOR"]
+        if [bus, dev, interface].iter().all(|f| !f.is_empty() && f.chars().all(|c| c.is_ascii_digit())))
+}
+
+/// Parses a textual descriptor dump, such as the output of `usbhid-dump`, into raw bytes.
+///
+/// Bytes are hex, optionally prefixed with `0x`, and separated by whitespace or commas. Anything
+/// after `//` or `#` on a line is ignored, and so are the header lines of `usbhid-dump`. A dump
+/// of several interfaces holds several descriptors, which fails: dump one interface at a time
+/// with `usbhid-dump -i`.
+pub fn descriptor_from_dump(text: &str) -> Result<Vec<u8>, UhidError> {
+    let mut rdesc = Vec::new();
+    let mut headers = 0;
+
+    for (number, line) in text.lines().enumerate() {
+        if is_dump_header(line) {
+            headers += 1;
+            if headers > 1 {
+                return Err(UhidError::InvalidDescriptor(format!(
+                    "second descriptor on line {}, the dump holds several interfaces",
+                    number + 1
+                )));
+            }
+            continue;
+        }
+        let line = line.split("//").next().unwrap();
+        let line = line.split('#').next().unwrap();
+
+        for token in line.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+            let hex = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")).unwrap_or(token);
+            match u8::from_str_radix(hex, 16) {
+                Ok(byte) if hex.len() <= 2 && hex.chars().all(|c| c.is_ascii_hexdigit()) => rdesc.push(byte),
+                _ => {
+                    return Err(UhidError::InvalidDescriptor(format!(
+                        "invalid byte {:?} on line {}",
+                        token,
+                        number + 1
+                    )))
+                }
+            }
+        }
+    }
+
+    Ok(rdesc)
+}
+
+/// Data, Variable, Absolute flags for [`ReportDescriptor::input`] and friends.
+pub const DATA_VAR_ABS: u8 = 0x02;
+/// Data, Variable, Relative.
+pub const DATA_VAR_REL: u8 = 0x06;
+/// Data, Array, Absolute.
+pub const DATA_ARR_ABS: u8 = 0x00;
+/// Constant, used for padding.
+pub const CONSTANT: u8 = 0x01;
+
+/// Kind of a collection item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collection {
+    Physical = 0,
+    Application = 1,
+    Logical = 2,
+}
+
+/// Builds a report descriptor item by item, using the shortest encoding for every value.
+///
+/// Composite devices can open their top-level collections with
+/// [`collection_with_report_id`](Self::collection_with_report_id), which hands out report IDs in
+/// order and remembers them by name.
+#[derive(Clone, Debug, Default)]
+pub struct ReportDescriptor {
+    data: Vec<u8>,
+    report_ids: Vec<(String, u8)>,
+}
+
+impl ReportDescriptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn item(&mut self, kind: u8, tag: u8, value: u32) -> &mut Self {
+        let bytes = value.to_le_bytes();
+        let size = match value {
+            0..=0xff => 1,
+            0x100..=0xffff => 2,
+            _ => 4,
+        };
+        self.push_item(kind, tag, &bytes[..size])
+    }
+
+    fn signed_item(&mut self, kind: u8, tag: u8, value: i32) -> &mut Self {
+        let bytes = value.to_le_bytes();
+        let size = match value {
+            -0x80..=0x7f => 1,
+            -0x8000..=0x7fff => 2,
+            _ => 4,
+        };
+        self.push_item(kind, tag, &bytes[..size])
+    }
+
+    fn push_item(&mut self, kind: u8, tag: u8, data: &[u8]) -> &mut Self {
+        let size = match data.len() {
+            4 => 3,
+            size => size as u8,
+        };
+        self.data.push(tag << 4 | kind << 2 | size);
+        self.data.extend_from_slice(data);
+        self
+    }
+
+    pub fn usage_page(&mut self, page: impl Into<u16>) -> &mut Self {
+        self.item(TYPE_GLOBAL, GLOBAL_USAGE_PAGE, page.into() as u32)
+    }
+
+    pub fn usage(&mut self, usage: impl Into<u16>) -> &mut Self {
+        self.item(TYPE_LOCAL, LOCAL_USAGE, usage.into() as u32)
+    }
+
+    pub fn usage_minimum(&mut self, usage: impl Into<u16>) -> &mut Self {
+        self.item(TYPE_LOCAL, LOCAL_USAGE_MINIMUM, usage.into() as u32)
+    }
+
+    pub fn usage_maximum(&mut self, usage: impl Into<u16>) -> &mut Self {
+        self.item(TYPE_LOCAL, LOCAL_USAGE_MAXIMUM, usage.into() as u32)
+    }
+
+    pub fn logical_minimum(&mut self, value: i32) -> &mut Self {
+        self.signed_item(TYPE_GLOBAL, GLOBAL_LOGICAL_MINIMUM, value)
+    }
+
+    pub fn logical_maximum(&mut self, value: i32) -> &mut Self {
+        self.signed_item(TYPE_GLOBAL, GLOBAL_LOGICAL_MAXIMUM, value)
+    }
+
+    pub fn report_size(&mut self, bits: u32) -> &mut Self {
+        self.item(TYPE_GLOBAL, GLOBAL_REPORT_SIZE, bits)
+    }
+
+    pub fn report_count(&mut self, count: u32) -> &mut Self {
+        self.item(TYPE_GLOBAL, GLOBAL_REPORT_COUNT, count)
+    }
+
+    pub fn input(&mut self, flags: u8) -> &mut Self {
+        self.item(TYPE_MAIN, MAIN_INPUT, flags as u32)
+    }
+
+    pub fn output(&mut self, flags: u8) -> &mut Self {
+        self.item(TYPE_MAIN, MAIN_OUTPUT, flags as u32)
+    }
+
+    pub fn feature(&mut self, flags: u8) -> &mut Self {
+        self.item(TYPE_MAIN, MAIN_FEATURE, flags as u32)
+    }
+
+    pub fn collection(&mut self, kind: Collection) -> &mut Self {
+        self.item(TYPE_MAIN, MAIN_COLLECTION, kind as u32)
+    }
+
+    pub fn end_collection(&mut self) -> &mut Self {
+        self.push_item(TYPE_MAIN, MAIN_END_COLLECTION, &[])
+    }
+
+    /// Opens a collection whose reports use the next free report ID, and returns that ID.
+    ///
+    /// The ID can be looked up again with [`report_id_for`](Self::report_id_for). Fails if `name`
+    /// is already taken or all 255 report IDs are in use.
+    pub fn collection_with_report_id(&mut self, name: &str, kind: Collection) -> Result<u8, UhidError> {
+        if self.report_id_for(name).is_some() {
+            return Err(UhidError::InvalidDescriptor(format!("duplicate collection name {:?}", name)));
+        }
+        let id = match u8::try_from(self.report_ids.len() + 1) {
+            Ok(id) => id,
+            Err(_) => return Err(UhidError::InvalidDescriptor("out of report IDs".to_string())),
+        };
+
+        self.collection(kind);
+        self.item(TYPE_GLOBAL, GLOBAL_REPORT_ID, id as u32);
+        self.report_ids.push((name.to_string(), id));
+        Ok(id)
+    }
+
+    /// Report ID assigned to the collection called `name`.
+    pub fn report_id_for(&self, name: &str) -> Option<u8> {
+        self.report_ids.iter().find(|(n, _)| n == name).map(|&(_, id)| id)
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+}
+
+/// Short item of a [`hid_descriptor!`](crate::hid_descriptor), with its data not yet encoded.
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub struct ShortItem {
+    prefix: u8,
+    value: i64,
+    /* whether the data is a two's complement value, which changes the shortest encoding */
+    signed: bool,
+    /* End Collection, Push and Pop carry no data at all */
+    empty: bool,
+}
+
+impl ShortItem {
+    const fn new(kind: u8, tag: u8, value: i64, signed: bool) -> Self {
+        ShortItem { prefix: tag << 4 | kind << 2, value, signed, empty: false }
+    }
+
+    const fn empty(kind: u8, tag: u8) -> Self {
+        ShortItem { prefix: tag << 4 | kind << 2, value: 0, signed: false, empty: true }
+    }
+
+    /// Data size in bytes, the same shortest encoding [`ReportDescriptor`] uses.
+    const fn data_size(&self) -> usize {
+        if self.empty {
+            0
+        } else if self.signed {
+            match self.value {
+                -0x80..=0x7f => 1,
+                -0x8000..=0x7fff => 2,
+                _ => 4,
+            }
+        } else {
+            match self.value {
+                0..=0xff => 1,
+                0x100..=0xffff => 2,
+                _ => 4,
+            }
+        }
+    }
+}
+
+/// Mnemonics accepted by [`hid_descriptor!`](crate::hid_descriptor).
+#[doc(hidden)]
+#[allow(non_snake_case)]
+pub mod items {
+    use super::*;
+
+    pub const fn UsagePage(page: u16) -> ShortItem {
+        ShortItem::new(TYPE_GLOBAL, GLOBAL_USAGE_PAGE, page as i64, false)
+    }
+    pub const fn Usage(usage: u16) -> ShortItem {
+        ShortItem::new(TYPE_LOCAL, LOCAL_USAGE, usage as i64, false)
+    }
+    pub const fn UsageMinimum(usage: u16) -> ShortItem {
+        ShortItem::new(TYPE_LOCAL, LOCAL_USAGE_MINIMUM, usage as i64, false)
+    }
+    pub const fn UsageMaximum(usage: u16) -> ShortItem {
+        ShortItem::new(TYPE_LOCAL, LOCAL_USAGE_MAXIMUM, usage as i64, false)
+    }
+    pub const fn LogicalMinimum(value: i32) -> ShortItem {
+        ShortItem::new(TYPE_GLOBAL, GLOBAL_LOGICAL_MINIMUM, value as i64, true)
+    }
+    pub const fn LogicalMaximum(value: i32) -> ShortItem {
+        ShortItem::new(TYPE_GLOBAL, GLOBAL_LOGICAL_MAXIMUM, value as i64, true)
+    }
+    pub const fn PhysicalMinimum(value: i32) -> ShortItem {
+        ShortItem::new(TYPE_GLOBAL, GLOBAL_PHYSICAL_MINIMUM, value as i64, true)
+    }
+    pub const fn PhysicalMaximum(value: i32) -> ShortItem {
+        ShortItem::new(TYPE_GLOBAL, GLOBAL_PHYSICAL_MAXIMUM, value as i64, true)
+    }
+    pub const fn UnitExponent(exponent: i32) -> ShortItem {
+        ShortItem::new(TYPE_GLOBAL, GLOBAL_UNIT_EXPONENT, exponent as i64, true)
+    }
+    pub const fn Unit(unit: u32) -> ShortItem {
+        ShortItem::new(TYPE_GLOBAL, GLOBAL_UNIT, unit as i64, false)
+    }
+    pub const fn ReportSize(bits: u32) -> ShortItem {
+        ShortItem::new(TYPE_GLOBAL, GLOBAL_REPORT_SIZE, bits as i64, false)
+    }
+    pub const fn ReportId(id: u8) -> ShortItem {
+        ShortItem::new(TYPE_GLOBAL, GLOBAL_REPORT_ID, id as i64, false)
+    }
+    pub const fn ReportCount(count: u32) -> ShortItem {
+        ShortItem::new(TYPE_GLOBAL, GLOBAL_REPORT_COUNT, count as i64, false)
+    }
+    pub const fn Push() -> ShortItem {
+        ShortItem::empty(TYPE_GLOBAL, GLOBAL_PUSH)
+    }
+    pub const fn Pop() -> ShortItem {
+        ShortItem::empty(TYPE_GLOBAL, GLOBAL_POP)
+    }
+    pub const fn Input(flags: u8) -> ShortItem {
+        ShortItem::new(TYPE_MAIN, MAIN_INPUT, flags as i64, false)
+    }
+    pub const fn Output(flags: u8) -> ShortItem {
+        ShortItem::new(TYPE_MAIN, MAIN_OUTPUT, flags as i64, false)
+    }
+    pub const fn Feature(flags: u8) -> ShortItem {
+        ShortItem::new(TYPE_MAIN, MAIN_FEATURE, flags as i64, false)
+    }
+    pub const fn Collection(kind: u8) -> ShortItem {
+        ShortItem::new(TYPE_MAIN, MAIN_COLLECTION, kind as i64, false)
+    }
+    pub const fn EndCollection() -> ShortItem {
+        ShortItem::empty(TYPE_MAIN, MAIN_END_COLLECTION)
+    }
+}
+
+/// Encoded length of `items`, panicking on unbalanced collections or Push and Pop items, which
+/// turns into a compile error in [`hid_descriptor!`](crate::hid_descriptor).
+#[doc(hidden)]
+pub const fn encoded_len(items: &[ShortItem]) -> usize {
+    const END_COLLECTION: u8 = MAIN_END_COLLECTION << 4 | TYPE_MAIN << 2;
+    const COLLECTION: u8 = MAIN_COLLECTION << 4 | TYPE_MAIN << 2;
+    const PUSH: u8 = GLOBAL_PUSH << 4 | TYPE_GLOBAL << 2;
+    const POP: u8 = GLOBAL_POP << 4 | TYPE_GLOBAL << 2;
+
+    let (mut len, mut depth, mut pushed) = (0, 0usize, 0usize);
+    let mut i = 0;
+    while i < items.len() {
+        match items[i].prefix {
+            COLLECTION => depth += 1,
+            END_COLLECTION if depth == 0 => panic!("End Collection without a matching Collection"),
+            END_COLLECTION => depth -= 1,
+            PUSH => pushed += 1,
+            POP if pushed == 0 => panic!("Pop without a matching Push"),
+            POP => pushed -= 1,
+            _ => (),
+        }
+        len += 1 + items[i].data_size();
+        i += 1;
+    }
+    if depth != 0 {
+        panic!("Collection without a matching End Collection");
+    }
+    len
+}
+
+/// Encodes `items`, `N` must be their [`encoded_len`].
+#[doc(hidden)]
+pub const fn encode<const N: usize>(items: &[ShortItem]) -> [u8; N] {
+    let mut rdesc = [0; N];
+    let (mut offset, mut i) = (0, 0);
+    while i < items.len() {
+        let size = items[i].data_size();
+        rdesc[offset] = items[i].prefix | if size == 4 { 3 } else { size as u8 };
+        let bytes = (items[i].value as u32).to_le_bytes();
+        let mut byte = 0;
+        while byte < size {
+            rdesc[offset + 1 + byte] = bytes[byte];
+            byte += 1;
+        }
+        offset += 1 + size;
+        i += 1;
+    }
+    rdesc
+}
+
+/// Builds a report descriptor at compile time from item mnemonics, as a `[u8; N]`.
+///
+/// Every item is written as its name in the HID specification, without spaces, followed by its
+/// value in parentheses: `UsagePage`, `Usage`, `UsageMinimum`, `UsageMaximum`, `LogicalMinimum`,
+/// `LogicalMaximum`, `PhysicalMinimum`, `PhysicalMaximum`, `UnitExponent`, `Unit`, `ReportSize`,
+/// `ReportId`, `ReportCount`, `Input`, `Output`, `Feature` and `Collection`. `EndCollection`,
+/// `Push` and `Pop` take no value. Values are encoded like [`ReportDescriptor`] does, in the
+/// fewest bytes, with the minimums, maximums and unit exponent as signed values.
+///
+/// An End Collection or Pop without a matching Collection or Push, or a Collection left open, is
+/// a compile error:
+///
+/// ```compile_fail
+/// const RDESC: [u8; 6] = uhid_rs::hid_descriptor! {
+///     UsagePage(0x01),
+///     Usage(0x02),
+///     Collection(0x01),
+/// };
+/// ```
+///
+/// ```
+/// use uhid_rs::{hid_descriptor, presets};
+/// use uhid_rs::descriptor::{CONSTANT, DATA_VAR_ABS, DATA_VAR_REL};
+///
+/// const MOUSE: [u8; 50] = hid_descriptor! {
+///     UsagePage(0x01),            // Generic Desktop
+///     Usage(0x02),                // Mouse
+///     Collection(0x01),           // Application
+///         Usage(0x01),            // Pointer
+///         Collection(0x00),       // Physical
+///             ReportCount(3),
+///             ReportSize(1),
+///             UsagePage(0x09),    // Button
+///             UsageMinimum(1),
+///             UsageMaximum(3),
+///             LogicalMinimum(0),
+///             LogicalMaximum(1),
+///             Input(DATA_VAR_ABS),
+///             ReportCount(1),
+///             ReportSize(5),
+///             Input(CONSTANT),
+///             ReportSize(8),
+///             ReportCount(2),
+///             UsagePage(0x01),    // Generic Desktop
+///             Usage(0x30),        // X
+///             Usage(0x31),        // Y
+///             LogicalMinimum(-127),
+///             LogicalMaximum(127),
+///             Input(DATA_VAR_REL),
+///         EndCollection,
+///     EndCollection,
+/// };
+///
+/// assert_eq!(MOUSE[..], presets::boot_mouse()[..]);
+/// ```
+#[macro_export]
+macro_rules! hid_descriptor {
+    ($($item:ident $(($value:expr))?),* $(,)?) => {{
+        const ITEMS: &[$crate::descriptor::ShortItem] = &[$($crate::descriptor::items::$item($($value)?)),*];
+        const LEN: usize = $crate::descriptor::encoded_len(ITEMS);
+        const RDESC: [u8; LEN] = $crate::descriptor::encode::<LEN>(ITEMS);
+        RDESC
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usages::{consumer, generic_desktop, UsagePage};
+
+    #[test]
+    fn mouse() {
+        let rdesc = [
+            0x05, 0x01,  // Usage Page (Generic Desktop)
+            0x09, 0x02,  // Usage (Mouse)
+            0xa1, 0x01,  // Collection (Application)
+            0x05, 0x09,  // .Usage Page (Button)
+            0x19, 0x01,  // .Usage Minimum (1)
+            0x29, 0x03,  // .Usage Maximum (3)
+            0x15, 0x00,  // .Logical Minimum (0)
+            0x25, 0x01,  // .Logical Maximum (1)
+            0x75, 0x01,  // .Report Size (1)
+            0x95, 0x03,  // .Report Count (3)
+            0x81, 0x02,  // .Input (Data,Var,Abs)
+            0x75, 0x05,  // .Report Size (5)
+            0x95, 0x01,  // .Report Count (1)
+            0x81, 0x03,  // .Input (Cnst,Var,Abs)
+            0x05, 0x01,  // .Usage Page (Generic Desktop)
+            0x09, 0x30,  // .Usage (X)
+            0x09, 0x31,  // .Usage (Y)
+            0x15, 0x81,  // .Logical Minimum (-127)
+            0x25, 0x7f,  // .Logical Maximum (127)
+            0x75, 0x08,  // .Report Size (8)
+            0x95, 0x02,  // .Report Count (2)
+            0x81, 0x06,  // .Input (Data,Var,Rel)
+            0xc0,        // End Collection
+        ];
+
+        let info = parse_descriptor(&rdesc).unwrap();
+        assert_eq!(
+            info.reports,
+            [ReportInfo { report_id: None, kind: ReportType::Input, bit_length: 24 }]
+        );
+        assert_eq!(info.reports[0].byte_length(), 3);
+    }
+
+    #[test]
+    fn classes() {
+        use crate::presets;
+
+        assert_eq!(descriptor_class(&presets::boot_mouse()).unwrap(), DeviceClass::Mouse);
+        assert_eq!(descriptor_class(&presets::boot_keyboard()).unwrap(), DeviceClass::Keyboard);
+        assert_eq!(descriptor_class(&presets::joystick()).unwrap(), DeviceClass::Gamepad);
+        assert_eq!(descriptor_class(&presets::consumer_control()).unwrap(), DeviceClass::Consumer);
+        assert_eq!(descriptor_class(&presets::dial()).unwrap(), DeviceClass::Generic);
+        assert_eq!(descriptor_class(&[]).unwrap(), DeviceClass::Generic);
+
+        let mut rdesc = presets::boot_keyboard();
+        rdesc.extend(presets::consumer_control());
+        assert_eq!(descriptor_class(&rdesc).unwrap(), DeviceClass::Composite);
+        let mut rdesc = presets::boot_mouse();
+        rdesc.extend(presets::wheel_mouse());
+        assert_eq!(descriptor_class(&rdesc).unwrap(), DeviceClass::Mouse);
+
+        /* extended usage, the page comes with it */
+        let rdesc = [0x05, 0x0c, 0x0b, 0x06, 0x00, 0x01, 0x00, 0xa1, 0x01, 0xc0];
+        assert_eq!(parse_descriptor(&rdesc).unwrap().applications, [0x0001_0006]);
+        assert_eq!(descriptor_class(&rdesc).unwrap(), DeviceClass::Keyboard);
+    }
+
+    #[test]
+    fn macro_encoding() {
+        const RDESC: [u8; 20] = crate::hid_descriptor! {
+            LogicalMinimum(-32767),
+            LogicalMaximum(0x10000),
+            Unit(0x1001),
+            UnitExponent(-4),
+            Push,
+            ReportId(3),
+            Pop,
+            Collection(0x00),
+            EndCollection,
+        };
+        assert_eq!(
+            RDESC,
+            [
+                0x16, 0x01, 0x80, 0x27, 0x00, 0x00, 0x01, 0x00, 0x66, 0x01, 0x10, 0x55, 0xfc, 0xa4, 0x85, 0x03, 0xb4,
+                0xa1, 0x00, 0xc0,
+            ]
+        );
+
+        let mut builder = ReportDescriptor::new();
+        builder.logical_minimum(-32767).logical_maximum(0x10000).collection(Collection::Physical).end_collection();
+        assert_eq!(builder.build(), [&RDESC[..3], &RDESC[3..8], &RDESC[17..]].concat());
+    }
+
+    #[test]
+    fn report_ids() {
+        let rdesc = [
+            0x85, 0x01,        // Report ID (1)
+            0x75, 0x08,        // Report Size (8)
+            0x95, 0x04,        // Report Count (4)
+            0x81, 0x02,        // Input (Data,Var,Abs)
+            0x95, 0x01,        // Report Count (1)
+            0x91, 0x02,        // Output (Data,Var,Abs)
+            0xa4,              // Push
+            0x85, 0x02,        // Report ID (2)
+            0x75, 0x01,        // Report Size (1)
+            0x96, 0x0c, 0x00,  // Report Count (12)
+            0xb1, 0x02,        // Feature (Data,Var,Abs)
+            0xb4,              // Pop
+            0x81, 0x02,        // Input (Data,Var,Abs)
+        ];
+
+        let info = parse_descriptor(&rdesc).unwrap();
+        assert_eq!(
+            info.reports,
+            [
+                ReportInfo { report_id: Some(1), kind: ReportType::Input, bit_length: 40 },
+                ReportInfo { report_id: Some(1), kind: ReportType::Output, bit_length: 8 },
+                ReportInfo { report_id: Some(2), kind: ReportType::Feature, bit_length: 12 },
+            ]
+        );
+        assert_eq!(info.report(Some(2), ReportType::Feature).unwrap().byte_length(), 3);
+        assert!(info.report(Some(2), ReportType::Input).is_none());
+    }
+
+    /* xorshift64, so failures reproduce without pulling in a property testing crate */
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
+
+    #[test]
+    fn random_bytes() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..10_000 {
+            let len = rng.below(64) as usize;
+            let rdesc: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+            let _ = parse_descriptor(&rdesc);
+        }
+    }
+
+    #[test]
+    fn random_descriptors() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..1_000 {
+            let mut rdesc = Vec::new();
+            let mut expected: Vec<ReportInfo> = Vec::new();
+            let numbered = rng.below(2) == 1;
+
+            for _ in 0..rng.below(8) {
+                let report_id = if numbered { Some(rng.below(4) as u8 + 1) } else { None };
+                let kind = [ReportType::Input, ReportType::Output, ReportType::Feature][rng.below(3) as usize];
+                let size = rng.below(33) as u32;
+                let count = rng.below(300) as u32;
+
+                if let Some(id) = report_id {
+                    rdesc.extend_from_slice(&[0x85, id]);
+                }
+                rdesc.push(0x77);
+                rdesc.extend_from_slice(&size.to_le_bytes());
+                rdesc.push(0x96);
+                rdesc.extend_from_slice(&(count as u16).to_le_bytes());
+                rdesc.extend_from_slice(match kind {
+                    ReportType::Input => &[0x81, 0x02],
+                    ReportType::Output => &[0x91, 0x02],
+                    ReportType::Feature => &[0xb1, 0x02],
+                });
+
+                match expected.iter_mut().find(|r| r.report_id == report_id && r.kind == kind) {
+                    Some(report) => report.bit_length += (size * count) as usize,
+                    None => expected.push(ReportInfo { report_id, kind, bit_length: (size * count) as usize }),
+                }
+            }
+
+            assert_eq!(parse_descriptor(&rdesc).unwrap().reports, expected);
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        let rdesc = [
+            0x77, 0xff, 0xff, 0xff, 0xff,  // Report Size (4294967295)
+            0x95, 0x02,                    // Report Count (2)
+            0x81, 0x02,                    // Input (Data,Var,Abs)
+        ];
+        assert!(parse_descriptor(&rdesc).is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse_descriptor(&[0x05]).is_err());
+        assert!(parse_descriptor(&[0x85, 0x00]).is_err());
+        assert!(parse_descriptor(&[0xb4]).is_err());
+        assert!(parse_descriptor(&[0xfe, 0x04, 0x00, 0x01]).is_err());
+    }
+
+    #[test]
+    fn build() {
+        let rdesc = ReportDescriptor::new()
+            .usage_page(UsagePage::GENERIC_DESKTOP)
+            .usage(generic_desktop::MOUSE)
+            .collection(Collection::Application)
+            .logical_minimum(-127)
+            .logical_maximum(127)
+            .report_size(8)
+            .report_count(0x100)
+            .input(DATA_VAR_REL)
+            .end_collection()
+            .build();
+
+        assert_eq!(
+            rdesc,
+            [0x05, 0x01, 0x09, 0x02, 0xa1, 0x01, 0x15, 0x81, 0x25, 0x7f, 0x75, 0x08, 0x96, 0x00, 0x01, 0x81, 0x06, 0xc0]
+        );
+    }
+
+    #[test]
+    fn report_id_assignment() {
+        let mut desc = ReportDescriptor::new();
+        desc.usage_page(UsagePage::GENERIC_DESKTOP).usage(generic_desktop::MOUSE);
+        let mouse = desc.collection_with_report_id("mouse", Collection::Application).unwrap();
+        desc.report_size(8).report_count(3).input(DATA_VAR_REL).end_collection();
+
+        desc.usage_page(UsagePage::CONSUMER).usage(consumer::CONSUMER_CONTROL);
+        let consumer = desc.collection_with_report_id("consumer", Collection::Application).unwrap();
+        desc.usage_minimum(0u16).usage_maximum(0x3ffu16);
+        desc.report_size(16).report_count(1).input(DATA_ARR_ABS).end_collection();
+
+        assert_eq!((mouse, consumer), (1, 2));
+        assert_eq!(desc.report_id_for("consumer"), Some(2));
+        assert_eq!(desc.report_id_for("keyboard"), None);
+        assert!(desc.collection_with_report_id("mouse", Collection::Application).is_err());
+
+        let info = parse_descriptor(&desc.build()).unwrap();
+        assert_eq!(info.report(Some(mouse), ReportType::Input).unwrap().byte_length(), 4);
+        assert_eq!(info.report(Some(consumer), ReportType::Input).unwrap().byte_length(), 3);
+    }
+
+    #[test]
+    fn check() {
+        let mut desc = ReportDescriptor::new();
+        let id = desc.collection_with_report_id("buttons", Collection::Application).unwrap();
+        desc.report_size(1).report_count(10).input(DATA_VAR_ABS).end_collection();
+        let info = parse_descriptor(&desc.build()).unwrap();
+
+        assert!(check_report(&info, Some(id), &[id, 0x00, 0x00]).is_ok());
+        match check_report(&info, Some(id), &[id, 0x00]) {
+            Err(UhidError::ReportLengthMismatch { expected: 3, got: 2 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match check_report(&info, None, &[0x00, 0x00]) {
+            Err(UhidError::UnknownReport(None)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dump() {
+        let text = "
+            # usbhid-dump output
+            05 01 09 02   a1 01\t// mouse
+            0x09,0x01, 0XA1 0x00  # pointer
+
+              c0 C0
+        ";
+        assert_eq!(
+            descriptor_from_dump(text).unwrap(),
+            [0x05, 0x01, 0x09, 0x02, 0xa1, 0x01, 0x09, 0x01, 0xa1, 0x00, 0xc0, 0xc0]
+        );
+        assert!(descriptor_from_dump("").unwrap().is_empty());
+
+        match descriptor_from_dump("05 01\n09 xx") {
+            Err(UhidError::InvalidDescriptor(reason)) => assert!(reason.contains("line 2")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(descriptor_from_dump("105").is_err());
+        assert!(descriptor_from_dump("+5").is_err());
+    }
+
+    #[test]
+    fn usbhid_dump() {
+        let text = "\
+001:004:000:DESCRIPTOR         1697371234.178935
+ 05 01 09 02 A1 01 09 01 A1 00 95 03 75 01 05 09
+ 19 01 29 03 15 00 25 01 81 02 95 01 75 05 81 01
+ 75 08 95 02 05 01 09 30 09 31 15 81 25 7F 81 06
+ C0 C0
+
+";
+        assert_eq!(descriptor_from_dump(text).unwrap(), crate::presets::boot_mouse());
+
+        let second = "001:004:001:DESCRIPTOR         1697371234.179102\n 05 0C 09 01 A1 01 C0\n";
+        match descriptor_from_dump(&format!("{}{}", text, second)) {
+            Err(UhidError::InvalidDescriptor(reason)) => assert!(reason.contains("line 7"), "{}", reason),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "root-tests")]
+    fn live_validation() {
+        validate_descriptor_live(&crate::presets::boot_mouse(), Duration::from_secs(5)).unwrap();
+
+        /* well formed, but more fields than the kernel's HID_MAX_USAGES */
+        let rdesc = [
+            0x06, 0x00, 0xff,  // Usage Page (Vendor Defined 0xff00)
+            0x09, 0x01,        // Usage (Vendor Usage 1)
+            0xa1, 0x01,        // Collection (Application)
+            0x15, 0x00,        // .Logical Minimum (0)
+            0x25, 0x01,        // .Logical Maximum (1)
+            0x75, 0x01,        // .Report Size (1)
+            0x96, 0x00, 0x40,  // .Report Count (16384)
+            0x09, 0x01,        // .Usage (Vendor Usage 1)
+            0x81, 0x02,        // .Input (Data,Var,Abs)
+            0xc0,              // End Collection
+        ];
+        parse_descriptor(&rdesc).unwrap();
+        match validate_descriptor_live(&rdesc, Duration::from_secs(1)) {
+            Err(UhidError::CreateRejected) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: MIT
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::HID_MAX_DESCRIPTOR_SIZE;
+
+#[derive(Debug)]
+pub enum UhidError {
+    Open(io::Error),
+    Epoll(io::Error),
+    /// The epoll instance could not be created, [`PollDevice`](crate::PollDevice) can be used instead.
+    EpollUnavailable(io::Error),
+    /// The kernel refused the Create2 event.
+    Create(io::Error),
+    /// The kernel accepted the Create2 event but stopped the device before starting it.
+    CreateRejected,
+    /// An input report could not be sent.
+    Input(io::Error),
+    /// Any other event could not be sent.
+    Write(io::Error),
+    /// Reading an event failed.
+    Read(io::Error),
+    Fcntl(io::Error),
+    Serialize(bincode::Error),
+    AlreadyCreated,
+    NotCreated,
+    DeviceGone,
+    QueueFull,
+    UnexpectedEof,
+    Timeout,
+    InvalidNameLength(usize),
+    StringTooLong { field: &'static str, len: usize, max: usize },
+    InvalidDescriptorLength(usize),
+    /// The report descriptor is empty, most likely it was never loaded.
+    EmptyDescriptor,
+    /// Report data longer than the kernel accepts in an event.
+    ReportTooLong(usize),
+    UnsupportedFlags(u64),
+    InvalidDescriptor(String),
+    InvalidEvent(String),
+    ReportOverflow { bit: usize, len: usize },
+    ReportLengthMismatch { expected: usize, got: usize },
+    ValueOutOfRange { value: i64, min: i64, max: i64 },
+    UnknownReport(Option<u8>),
+    UnknownBus(String),
+    InvalidAddress(String),
+    InvalidId(String),
+    InvalidCountry(u32),
+    SysfsNotFound,
+    /// A sysfs attribute of the device could not be read.
+    Sysfs(io::Error),
+    UnmappableChar(char),
+    /// A keyboard report can't hold another pressed key.
+    KeyRollover,
+    UnknownDevice(String),
+    /// The operation does not apply to this kind of device.
+    Unsupported(&'static str),
+    /// A device definition file could not be read or written.
+    ConfigFile(io::Error),
+    InvalidConfig { line: usize, reason: String },
+}
+
+impl fmt::Display for UhidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UhidError::Open(e) => write!(f, "failed to open the UHID file descriptor ({})", e),
+            UhidError::Epoll(e) => write!(f, "epoll failed ({})", e),
+            UhidError::EpollUnavailable(e) => write!(f, "epoll is unavailable ({})", e),
+            UhidError::Create(e) => write!(f, "failed to create device ({})", e),
+            UhidError::CreateRejected => write!(f, "the kernel stopped the device before starting it"),
+            UhidError::Input(e) => write!(f, "failed to send input report ({})", e),
+            UhidError::Write(e) => write!(f, "failed to send event ({})", e),
+            UhidError::Read(e) => write!(f, "failed to read event ({})", e),
+            UhidError::Fcntl(e) => write!(f, "failed to access the file descriptor flags ({})", e),
+            UhidError::Serialize(e) => write!(f, "failed to serialize event ({})", e),
+            UhidError::AlreadyCreated => write!(f, "device already created"),
+            UhidError::NotCreated => write!(f, "device not created"),
+            UhidError::DeviceGone => write!(f, "device is gone"),
+            UhidError::UnexpectedEof => write!(f, "end of file while reading an event"),
+            UhidError::QueueFull => write!(f, "kernel event queue is full"),
+            UhidError::Timeout => write!(f, "timed out"),
+            UhidError::InvalidNameLength(len) => write!(f, "invalid name length: {} (max: 127)", len),
+            UhidError::StringTooLong { field, len, max } => {
+                write!(f, "{} is too long: {} bytes (max: {})", field, len, max)
+            }
+            UhidError::InvalidDescriptorLength(len) => write!(
+                f,
+                "invalid report descriptor length: {} (max: {})",
+                len, HID_MAX_DESCRIPTOR_SIZE
+            ),
+            UhidError::EmptyDescriptor => write!(f, "the report descriptor is empty"),
+            UhidError::ReportTooLong(len) => {
+                write!(f, "report too long: {} bytes (max: {})", len, HID_MAX_DESCRIPTOR_SIZE)
+            }
+            UhidError::UnsupportedFlags(flags) => {
+                write!(f, "creation flags {:#x} are not supported by the kernel", flags)
+            }
+            UhidError::InvalidDescriptor(reason) => write!(f, "invalid report descriptor ({})", reason),
+            UhidError::InvalidEvent(reason) => write!(f, "invalid event ({})", reason),
+            UhidError::ReportOverflow { bit, len } => {
+                write!(f, "write up to bit {} is past the end of a {}-byte report", bit, len)
+            }
+            UhidError::ReportLengthMismatch { expected, got } => {
+                write!(f, "report is {} bytes long, the descriptor says {}", got, expected)
+            }
+            UhidError::ValueOutOfRange { value, min, max } => {
+                write!(f, "value {} is out of range ({}..={})", value, min, max)
+            }
+            UhidError::UnknownReport(Some(id)) => write!(f, "no input report with ID {}", id),
+            UhidError::UnknownReport(None) => write!(f, "no unnumbered input report"),
+            UhidError::UnknownBus(name) => write!(f, "unknown bus: {:?}", name),
+            UhidError::InvalidId(id) => write!(f, "invalid vendor:product ID: {:?}", id),
+            UhidError::InvalidAddress(address) => write!(f, "invalid Bluetooth address: {:?}", address),
+            UhidError::InvalidCountry(country) => write!(f, "invalid country code: {} (max: 35)", country),
+            UhidError::SysfsNotFound => write!(f, "device not found in sysfs"),
+            UhidError::Sysfs(e) => write!(f, "failed to read from sysfs ({})", e),
+            UhidError::ConfigFile(e) => write!(f, "failed to access the device definition ({})", e),
+            UhidError::InvalidConfig { line, reason } => {
+                write!(f, "invalid device definition, line {}: {}", line, reason)
+            }
+            UhidError::Unsupported(what) => write!(f, "{} is not supported", what),
+            UhidError::UnknownDevice(name) => write!(f, "unknown device: {}", name),
+            UhidError::KeyRollover => write!(f, "too many keys held (max: 6)"),
+            UhidError::UnmappableChar(c) => write!(f, "no key types {:?} on a US keyboard", c),
+        }
+    }
+}
+
+impl Error for UhidError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            UhidError::Open(e)
+            | UhidError::Epoll(e)
+            | UhidError::EpollUnavailable(e)
+            | UhidError::ConfigFile(e)
+            | UhidError::Sysfs(e)
+            | UhidError::Create(e)
+            | UhidError::Input(e)
+            | UhidError::Write(e)
+            | UhidError::Read(e)
+            | UhidError::Fcntl(e) => Some(e),
+            UhidError::Serialize(e) => Some(e),
+            _ => None,
+        }
+    }
+}
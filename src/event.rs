@@ -0,0 +1,400 @@
+// SPDX-License-Identifier: MIT
+
+use std::convert::TryInto;
+use std::time::Duration;
+
+use bitflags::bitflags;
+
+use crate::{Device, EventType, ReportType, Transport, UhidError, HID_MAX_DESCRIPTOR_SIZE, LEGACY_CREATE_SIZE};
+
+/// Size of `struct uhid_event` from `<linux/uhid.h>`, the 4-byte type followed by the largest
+/// request, `struct uhid_create2_req`.
+///
+/// The struct is packed and its size is part of the UHID ABI: the kernel sends events of exactly
+/// this size and ignores anything past it in a write, so it is the same on every kernel with
+/// Create2 (3.18 and newer). It is vendored here rather than read from the headers at build time, and
+/// checked at compile time against the layout this crate serializes.
+pub const UHID_EVENT_SIZE: usize = 4 + 4372;
+
+const UHID_DATA_MAX: usize = HID_MAX_DESCRIPTOR_SIZE;
+
+/// Raw values of the `type` field of `struct uhid_event`, for code driving the file descriptor
+/// directly.
+pub mod event_type {
+    use crate::EventType;
+
+    pub const CREATE: u32 = EventType::__LegacyCreate as u32;
+    pub const DESTROY: u32 = EventType::Destroy as u32;
+    pub const START: u32 = EventType::Start as u32;
+    pub const STOP: u32 = EventType::Stop as u32;
+    pub const OPEN: u32 = EventType::Open as u32;
+    pub const CLOSE: u32 = EventType::Close as u32;
+    pub const OUTPUT: u32 = EventType::Output as u32;
+    pub const OUTPUT_EV: u32 = EventType::__LegacyOutputEv as u32;
+    pub const INPUT: u32 = EventType::__LegacyInput as u32;
+    pub const GET_REPORT: u32 = EventType::GetReport as u32;
+    pub const GET_REPORT_REPLY: u32 = EventType::GetReportReply as u32;
+    pub const CREATE2: u32 = EventType::Create2 as u32;
+    pub const INPUT2: u32 = EventType::Input2 as u32;
+    pub const SET_REPORT: u32 = EventType::SetReport as u32;
+    pub const SET_REPORT_REPLY: u32 = EventType::SetReportReply as u32;
+}
+
+/// Largest event of the raw type `raw_type` (one of the [`event_type`] values), the 4-byte type
+/// included, so a buffer of this size holds any event of that type.
+///
+/// Report data always takes the full `UHID_DATA_MAX` bytes here, an event carrying a shorter
+/// report may be cut after it for the types that have the size before the data. Unknown types
+/// get [`UHID_EVENT_SIZE`], enough for any event.
+pub const fn event_buffer_size(raw_type: u32) -> usize {
+    let body = match raw_type {
+        event_type::CREATE => return LEGACY_CREATE_SIZE,
+        event_type::CREATE2 => return UHID_EVENT_SIZE,
+        event_type::DESTROY | event_type::STOP | event_type::OPEN | event_type::CLOSE => 0,
+        /* dev_flags */
+        event_type::START => 8,
+        /* data, size, rtype */
+        event_type::OUTPUT => UHID_DATA_MAX + 2 + 1,
+        /* type, code, value */
+        event_type::OUTPUT_EV => 2 + 2 + 4,
+        /* data, size */
+        event_type::INPUT => UHID_DATA_MAX + 2,
+        /* size, data */
+        event_type::INPUT2 => 2 + UHID_DATA_MAX,
+        /* id, rnum, rtype */
+        event_type::GET_REPORT => 4 + 1 + 1,
+        /* id, err, size, data */
+        event_type::GET_REPORT_REPLY => 4 + 2 + 2 + UHID_DATA_MAX,
+        /* id, rnum, rtype, size, data */
+        event_type::SET_REPORT => 4 + 1 + 1 + 2 + UHID_DATA_MAX,
+        /* id, err */
+        event_type::SET_REPORT_REPLY => 4 + 2,
+        _ => return UHID_EVENT_SIZE,
+    };
+    4 + body
+}
+
+bitflags! {
+    /// Device flags of the Start event, the `UHID_DEV_*` values.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct StartFlags: u64 {
+        const NUMBERED_FEATURE_REPORTS = 1 << 0;
+        const NUMBERED_OUTPUT_REPORTS = 1 << 1;
+        const NUMBERED_INPUT_REPORTS = 1 << 2;
+    }
+}
+
+/// Event sent by the kernel.
+///
+/// Report data is copied out of the read buffer, so events can be kept around freely.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The HID driver started using the device.
+    Start { dev_flags: StartFlags },
+    /// The HID driver stopped using the device.
+    Stop,
+    /// The first consumer opened the device.
+    Open,
+    /// The last consumer closed the device.
+    Close,
+    /// The host sent a report to the device.
+    Output { data: Vec<u8>, rtype: ReportType },
+    /// The host asks for a report, answer with [`Device::get_report_reply`](crate::Device::get_report_reply).
+    GetReport { id: u32, rnum: u8, rtype: ReportType },
+    /// The host sets a report, answer with [`Device::set_report_reply`](crate::Device::set_report_reply).
+    SetReport { id: u32, rnum: u8, rtype: ReportType, data: Vec<u8> },
+}
+
+impl ReportType {
+    fn from_u8(value: u8) -> Result<Self, UhidError> {
+        match value {
+            0 => Ok(ReportType::Feature),
+            1 => Ok(ReportType::Output),
+            2 => Ok(ReportType::Input),
+            _ => Err(UhidError::InvalidEvent(format!("unknown report type {}", value))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ReportType::Feature => "feature",
+            ReportType::Output => "output",
+            ReportType::Input => "input",
+        }
+    }
+}
+
+fn byte_count(len: usize) -> String {
+    match len {
+        1 => "1 byte".to_string(),
+        len => format!("{} bytes", len),
+    }
+}
+
+fn field(buf: &[u8], start: usize, len: usize) -> Result<&[u8], UhidError> {
+    match buf.get(start..start + len) {
+        Some(field) => Ok(field),
+        None => Err(UhidError::InvalidEvent(format!("event too short ({} bytes)", buf.len()))),
+    }
+}
+
+fn u16_field(buf: &[u8], start: usize) -> Result<u16, UhidError> {
+    Ok(u16::from_le_bytes(field(buf, start, 2)?.try_into().unwrap()))
+}
+
+fn u32_field(buf: &[u8], start: usize) -> Result<u32, UhidError> {
+    Ok(u32::from_le_bytes(field(buf, start, 4)?.try_into().unwrap()))
+}
+
+impl Event {
+    /// Parses a `struct uhid_event` as read from the kernel.
+    pub(crate) fn parse(buf: &[u8]) -> Result<Self, UhidError> {
+        let raw_type = u32_field(buf, 0)?;
+        let event_type = match EventType::from_u32(raw_type) {
+            Some(event_type) => event_type,
+            None => return Err(UhidError::InvalidEvent(format!("unknown event type {}", raw_type))),
+        };
+
+        match event_type {
+            EventType::Start => {
+                let flags = u64::from_le_bytes(field(buf, 4, 8)?.try_into().unwrap());
+                Ok(Event::Start { dev_flags: StartFlags::from_bits_retain(flags) })
+            }
+            EventType::Stop => Ok(Event::Stop),
+            EventType::Open => Ok(Event::Open),
+            EventType::Close => Ok(Event::Close),
+            EventType::Output => {
+                /* struct uhid_output_req: data[UHID_DATA_MAX], size, rtype */
+                let size = u16_field(buf, 4 + UHID_DATA_MAX)? as usize;
+                let rtype = ReportType::from_u8(field(buf, 4 + UHID_DATA_MAX + 2, 1)?[0])?;
+                let data = field(buf, 4, size.min(UHID_DATA_MAX))?.to_vec();
+                Ok(Event::Output { data, rtype })
+            }
+            EventType::GetReport => Ok(Event::GetReport {
+                id: u32_field(buf, 4)?,
+                rnum: field(buf, 8, 1)?[0],
+                rtype: ReportType::from_u8(field(buf, 9, 1)?[0])?,
+            }),
+            EventType::SetReport => {
+                let size = u16_field(buf, 10)? as usize;
+                Ok(Event::SetReport {
+                    id: u32_field(buf, 4)?,
+                    rnum: field(buf, 8, 1)?[0],
+                    rtype: ReportType::from_u8(field(buf, 9, 1)?[0])?,
+                    data: field(buf, 12, size.min(UHID_DATA_MAX))?.to_vec(),
+                })
+            }
+            _ => Err(UhidError::InvalidEvent(format!("unexpected {:?} event", event_type))),
+        }
+    }
+
+    /// Short description of the event for logs, with the size of the report data instead of the
+    /// data itself, such as `GetReport(id=5, rnum=2, feature)` or `Output(output, 3 bytes)`.
+    pub fn summary(&self) -> String {
+        match self {
+            Event::Start { dev_flags } => format!("Start(flags={:#x})", dev_flags.bits()),
+            Event::Stop => "Stop".to_string(),
+            Event::Open => "Open".to_string(),
+            Event::Close => "Close".to_string(),
+            Event::Output { data, rtype } => format!("Output({}, {})", rtype.name(), byte_count(data.len())),
+            Event::GetReport { id, rnum, rtype } => format!("GetReport(id={}, rnum={}, {})", id, rnum, rtype.name()),
+            Event::SetReport { id, rnum, rtype, data } => {
+                format!("SetReport(id={}, rnum={}, {}, {})", id, rnum, rtype.name(), byte_count(data.len()))
+            }
+        }
+    }
+
+    /// Encodes the event the way the kernel sends it, padded to the full `struct uhid_event` size.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(UHID_EVENT_SIZE);
+        match self {
+            Event::Start { dev_flags } => {
+                buf.extend_from_slice(&(EventType::Start as u32).to_le_bytes());
+                buf.extend_from_slice(&dev_flags.bits().to_le_bytes());
+            }
+            Event::Stop => buf.extend_from_slice(&(EventType::Stop as u32).to_le_bytes()),
+            Event::Open => buf.extend_from_slice(&(EventType::Open as u32).to_le_bytes()),
+            Event::Close => buf.extend_from_slice(&(EventType::Close as u32).to_le_bytes()),
+            Event::Output { data, rtype } => {
+                buf.extend_from_slice(&(EventType::Output as u32).to_le_bytes());
+                buf.extend_from_slice(data);
+                buf.resize(4 + UHID_DATA_MAX, 0);
+                buf.extend_from_slice(&(data.len() as u16).to_le_bytes());
+                buf.push(*rtype as u8);
+            }
+            Event::GetReport { id, rnum, rtype } => {
+                buf.extend_from_slice(&(EventType::GetReport as u32).to_le_bytes());
+                buf.extend_from_slice(&id.to_le_bytes());
+                buf.push(*rnum);
+                buf.push(*rtype as u8);
+            }
+            Event::SetReport { id, rnum, rtype, data } => {
+                buf.extend_from_slice(&(EventType::SetReport as u32).to_le_bytes());
+                buf.extend_from_slice(&id.to_le_bytes());
+                buf.push(*rnum);
+                buf.push(*rtype as u8);
+                buf.extend_from_slice(&(data.len() as u16).to_le_bytes());
+                buf.extend_from_slice(data);
+            }
+        }
+        buf.resize(UHID_EVENT_SIZE, 0);
+        buf
+    }
+}
+
+/// Iterator over events that blocks until the next one arrives, see [`Device::blocking_events`].
+///
+/// Ends after yielding a Stop event or an error.
+pub struct BlockingEvents<'a, T> {
+    pub(crate) device: &'a mut Device<T>,
+    pub(crate) done: bool,
+}
+
+impl<T: Transport> Iterator for BlockingEvents<'_, T> {
+    type Item = Result<Event, UhidError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.device.read_event();
+        self.done = matches!(result, Ok(Event::Stop) | Err(_));
+        Some(result)
+    }
+}
+
+/// Iterator over the events that are already pending, see [`Device::nonblocking_events`].
+///
+/// Ends as soon as no event is pending, or after yielding an error.
+pub struct NonblockingEvents<'a, T> {
+    pub(crate) device: &'a mut Device<T>,
+    pub(crate) done: bool,
+}
+
+impl<T: Transport> Iterator for NonblockingEvents<'_, T> {
+    type Item = Result<Event, UhidError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let result = match self.device.uhid_fd.wait_readable(Some(Duration::ZERO)) {
+                Ok(true) => self.device.read_one_event(),
+                Ok(false) => return None,
+                Err(e) => Err(UhidError::Read(e)),
+            };
+            match result {
+                /* answered automatically, look for the next one */
+                Ok(None) => (),
+                Ok(Some(event)) => return Some(Ok(event)),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTransport;
+
+    #[test]
+    fn buffer_sizes() {
+        assert_eq!(event_buffer_size(event_type::DESTROY), 4);
+        assert_eq!(event_buffer_size(event_type::CREATE2), UHID_EVENT_SIZE);
+        assert_eq!(event_buffer_size(event_type::INPUT2), 4102);
+        assert_eq!(event_buffer_size(event_type::SET_REPORT_REPLY), 10);
+        assert_eq!(event_buffer_size(99), UHID_EVENT_SIZE);
+
+        /* the largest events the device writes and the kernel sends fit exactly */
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create_with(&crate::DeviceConfig { rdesc: vec![0x05, 0x01, 0xc0], ..Default::default() }).unwrap();
+        dev.input(&[0; UHID_DATA_MAX]).unwrap();
+        dev.get_report_reply(1, 0, &[0; UHID_DATA_MAX]).unwrap();
+        let written = mock.written();
+        assert_eq!(written[1].len(), event_buffer_size(event_type::INPUT2));
+        assert_eq!(written[2].len(), event_buffer_size(event_type::GET_REPORT_REPLY));
+
+        let event = Event::Output { data: vec![0; UHID_DATA_MAX], rtype: ReportType::Output };
+        let size = event_buffer_size(event_type::OUTPUT);
+        assert_eq!(Event::parse(&event.to_bytes()[..size]).unwrap(), event);
+    }
+
+    #[test]
+    fn summary() {
+        let events = [
+            (Event::Start { dev_flags: StartFlags::NUMBERED_INPUT_REPORTS }, "Start(flags=0x4)"),
+            (Event::Start { dev_flags: StartFlags::empty() }, "Start(flags=0x0)"),
+            (Event::Stop, "Stop"),
+            (Event::Open, "Open"),
+            (Event::Close, "Close"),
+            (Event::Output { data: vec![1, 2, 3], rtype: ReportType::Feature }, "Output(feature, 3 bytes)"),
+            (Event::Output { data: vec![1], rtype: ReportType::Output }, "Output(output, 1 byte)"),
+            (Event::GetReport { id: 5, rnum: 2, rtype: ReportType::Feature }, "GetReport(id=5, rnum=2, feature)"),
+            (
+                Event::SetReport { id: 7, rnum: 0, rtype: ReportType::Output, data: vec![] },
+                "SetReport(id=7, rnum=0, output, 0 bytes)",
+            ),
+        ];
+        for (event, summary) in &events {
+            assert_eq!(event.summary(), *summary);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let events = [
+            Event::Start { dev_flags: StartFlags::NUMBERED_INPUT_REPORTS },
+            Event::Stop,
+            Event::Open,
+            Event::Close,
+            Event::Output { data: vec![0x01, 0x02], rtype: ReportType::Output },
+            Event::GetReport { id: 3, rnum: 4, rtype: ReportType::Feature },
+            Event::SetReport { id: 5, rnum: 6, rtype: ReportType::Feature, data: vec![0x07] },
+        ];
+
+        for event in &events {
+            let bytes = event.to_bytes();
+            assert_eq!(bytes.len(), UHID_EVENT_SIZE);
+            assert_eq!(&Event::parse(&bytes).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn layout() {
+        let bytes = Event::GetReport { id: 0x01020304, rnum: 5, rtype: ReportType::Input }.to_bytes();
+        assert_eq!(&bytes[..10], &[9, 0, 0, 0, 0x04, 0x03, 0x02, 0x01, 5, 2]);
+
+        let bytes = Event::Output { data: vec![0xaa], rtype: ReportType::Output }.to_bytes();
+        assert_eq!(&bytes[4..6], &[0xaa, 0x00]);
+        assert_eq!(&bytes[4100..4103], &[1, 0, 1]);
+    }
+
+    #[test]
+    fn event_types() {
+        /* values from <linux/uhid.h> */
+        assert_eq!(event_type::CREATE, 0);
+        assert_eq!(event_type::DESTROY, 1);
+        assert_eq!(event_type::OUTPUT, 6);
+        assert_eq!(event_type::GET_REPORT, 9);
+        assert_eq!(event_type::CREATE2, 11);
+        assert_eq!(event_type::INPUT2, 12);
+        assert_eq!(event_type::SET_REPORT_REPLY, 14);
+
+        for (value, event_type) in EventType::ALL.iter().enumerate() {
+            assert_eq!(EventType::from_u32(value as u32), Some(*event_type));
+        }
+        assert_eq!(EventType::from_u32(15), None);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Event::parse(&[2, 0]).is_err());
+        assert!(Event::parse(&[2, 0, 0, 0, 1]).is_err());
+        assert!(Event::parse(&[99, 0, 0, 0]).is_err());
+        assert!(Event::parse(&[9, 0, 0, 0, 0, 0, 0, 0, 0, 7]).is_err());
+    }
+}
@@ -0,0 +1,396 @@
+// SPDX-License-Identifier: MIT
+
+use std::fs::File;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use bitflags::bitflags;
+
+use crate::presets::{self, KeyboardReport, Protocol};
+use crate::{Device, DeviceConfig, Event, ReportType, Transport, UhidError};
+
+/// Most non-modifier keys a boot keyboard report can hold.
+pub const MAX_KEYS: usize = 6;
+
+/// Shortest key repeat interval [`Keyboard::set_repeat`] uses, the 1 ms polling interval of a
+/// full-speed USB device.
+pub const MIN_REPEAT_RATE: Duration = Duration::from_millis(1);
+
+bitflags! {
+    /// LED states of the [`presets::boot_keyboard`] output report.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct KeyboardLeds: u8 {
+        const NUM_LOCK = 1 << 0;
+        const CAPS_LOCK = 1 << 1;
+        const SCROLL_LOCK = 1 << 2;
+        const COMPOSE = 1 << 3;
+        const KANA = 1 << 4;
+    }
+}
+
+/// Keyboard page usage of a modifier key, Left Control (0xe0) through Right GUI (0xe7).
+fn modifier_bit(keycode: u8) -> Option<u8> {
+    match keycode {
+        0xe0..=0xe7 => Some(1 << (keycode - 0xe0)),
+        _ => None,
+    }
+}
+
+type RepeatSpawner<T> = fn(Arc<Mutex<Device<T>>>, Vec<u8>, Duration, Duration) -> RepeatTimer;
+
+struct RepeatTimer {
+    /* dropping it wakes the thread up to exit */
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<Result<(), UhidError>>,
+}
+
+impl RepeatTimer {
+    fn stop(self) -> Result<(), UhidError> {
+        drop(self.stop);
+        self.thread.join().expect("key repeat thread panicked")
+    }
+}
+
+/// Resends `report` every `rate` after `delay`, until the sending half of the channel is dropped.
+fn spawn_repeat<T: Transport + Send + 'static>(
+    device: Arc<Mutex<Device<T>>>,
+    report: Vec<u8>,
+    delay: Duration,
+    rate: Duration,
+) -> RepeatTimer {
+    let (stop, stopped) = mpsc::channel();
+    let thread = thread::spawn(move || {
+        /* keep to a deadline, so slow writes don't make the rate drift */
+        let mut deadline = Instant::now() + delay;
+        loop {
+            match stopped.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Err(RecvTimeoutError::Timeout) => (),
+                _ => return Ok(()),
+            }
+            device.lock().unwrap().input(&report)?;
+            deadline += rate;
+        }
+    });
+    RepeatTimer { stop, thread }
+}
+
+/// Keyboard that keeps track of the held keys, sending a [`presets::boot_keyboard`] report on
+/// every change.
+pub struct Keyboard<T = File> {
+    /* shared with the key repeat thread */
+    device: Arc<Mutex<Device<T>>>,
+    modifiers: u8,
+    /* in the order they were pressed */
+    keys: Vec<u8>,
+    leds: KeyboardLeds,
+    repeat: Option<(Duration, Duration, RepeatSpawner<T>)>,
+    repeat_timer: Option<RepeatTimer>,
+}
+
+impl<T: Transport> Keyboard<T> {
+    /// Creates the device with `config`, replacing its report descriptor with the boot keyboard.
+    pub fn create(mut device: Device<T>, config: &DeviceConfig) -> Result<Self, UhidError> {
+        device.create_with(&DeviceConfig { rdesc: presets::boot_keyboard(), ..config.clone() })?;
+        Ok(Keyboard {
+            device: Arc::new(Mutex::new(device)),
+            modifiers: 0,
+            keys: Vec::with_capacity(MAX_KEYS),
+            leds: KeyboardLeds::empty(),
+            repeat: None,
+            repeat_timer: None,
+        })
+    }
+
+    /// The device, locked. Key repeat reports wait while the guard is held.
+    pub fn device(&mut self) -> MutexGuard<'_, Device<T>> {
+        self.device.lock().unwrap()
+    }
+
+    /// Stops key repeat and gives back the device.
+    pub fn into_device(mut self) -> Result<Device<T>, UhidError> {
+        self.stop_repeat()?;
+        /* the repeat thread has exited and dropped its reference */
+        match Arc::try_unwrap(self.device) {
+            Ok(device) => Ok(device.into_inner().unwrap()),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    pub fn is_pressed(&self, keycode: u8) -> bool {
+        match modifier_bit(keycode) {
+            Some(bit) => self.modifiers & bit != 0,
+            None => self.keys.contains(&keycode),
+        }
+    }
+
+    /// LED states last set by the host, see [`handle_event`](Self::handle_event).
+    pub fn leds(&self) -> KeyboardLeds {
+        self.leds
+    }
+
+    /// Stops resending held keys, see [`set_repeat`](Self::set_repeat).
+    pub fn clear_repeat(&mut self) -> Result<(), UhidError> {
+        self.repeat = None;
+        self.stop_repeat()
+    }
+
+    /// Returns the error that ended the repeat thread, if any.
+    fn stop_repeat(&mut self) -> Result<(), UhidError> {
+        match self.repeat_timer.take() {
+            Some(timer) => timer.stop(),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends the current state, then resends it while a non-modifier key is held if key repeat
+    /// is on and `repeat` is set.
+    ///
+    /// An error that ended the previous repeat is returned after the new state is sent, so the
+    /// host still sees the held keys the keyboard records.
+    fn send(&mut self, repeat: bool) -> Result<(), UhidError> {
+        let stopped = self.stop_repeat();
+        let mut report = KeyboardReport { modifiers: self.modifiers, keys: [0; MAX_KEYS] };
+        report.keys[..self.keys.len()].copy_from_slice(&self.keys);
+        let report = report.to_bytes(Protocol::Boot, None);
+        self.device.lock().unwrap().input(&report)?;
+        if let (true, Some((delay, rate, spawn))) = (repeat, self.repeat) {
+            self.repeat_timer = Some(spawn(self.device.clone(), report, delay, rate));
+        }
+        stopped
+    }
+
+    /// Presses the key with the keyboard page usage `keycode`, modifiers included.
+    ///
+    /// Pressing a key that is already held does nothing. With [`MAX_KEYS`] non-modifier keys held,
+    /// pressing another fails with [`UhidError::KeyRollover`] and nothing is sent.
+    pub fn press(&mut self, keycode: u8) -> Result<(), UhidError> {
+        if self.is_pressed(keycode) {
+            return Ok(());
+        }
+        match modifier_bit(keycode) {
+            Some(bit) => self.modifiers |= bit,
+            None if self.keys.len() == MAX_KEYS => return Err(UhidError::KeyRollover),
+            None => self.keys.push(keycode),
+        }
+        self.send(modifier_bit(keycode).is_none())
+    }
+
+    /// Releases `keycode`, releasing a key that isn't held does nothing.
+    pub fn release(&mut self, keycode: u8) -> Result<(), UhidError> {
+        if !self.is_pressed(keycode) {
+            return Ok(());
+        }
+        match modifier_bit(keycode) {
+            Some(bit) => self.modifiers &= !bit,
+            None => self.keys.retain(|&key| key != keycode),
+        }
+        self.send(false)
+    }
+
+    /// Presses and releases `keycode`.
+    pub fn tap(&mut self, keycode: u8) -> Result<(), UhidError> {
+        self.press(keycode)?;
+        self.release(keycode)
+    }
+
+    /// Releases every held key.
+    pub fn release_all(&mut self) -> Result<(), UhidError> {
+        self.modifiers = 0;
+        self.keys.clear();
+        self.send(false)
+    }
+
+    /// Updates the LED states if `event` is an output report, returns whether it was one.
+    ///
+    /// The kernel sends LED changes as [`Event::Output`]. [`Event::SetReport`] requests for the
+    /// output report are answered as well.
+    pub fn handle_event(&mut self, event: &Event) -> Result<bool, UhidError> {
+        match event {
+            Event::Output { data, rtype: ReportType::Output } if !data.is_empty() => {
+                self.leds = KeyboardLeds::from_bits_truncate(data[0]);
+                Ok(true)
+            }
+            Event::SetReport { id, rtype: ReportType::Output, data, .. } if !data.is_empty() => {
+                self.leds = KeyboardLeds::from_bits_truncate(data[0]);
+                self.device.lock().unwrap().set_report_reply(*id, 0)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+impl<T: Transport + Send + 'static> Keyboard<T> {
+    /// Resends the press report every `rate` while a key is held, starting `delay` after it was
+    /// pressed, from a background thread.
+    ///
+    /// Most hosts repeat held keys themselves and ignore a report identical to the previous one,
+    /// so this is only useful to emulate a device that repeats in hardware. Like on such devices,
+    /// only the last pressed key repeats: pressing or releasing any key, modifiers included,
+    /// stops the repeat, and pressing a non-modifier key starts it over. A failed repeat report
+    /// stops the repeat and is returned by the next call that changes the held keys. Takes effect
+    /// from the next key press.
+    ///
+    /// A `rate` below [`MIN_REPEAT_RATE`], such as zero, is raised to it rather than flooding the
+    /// kernel with reports.
+    pub fn set_repeat(&mut self, delay: Duration, rate: Duration) {
+        self.repeat = Some((delay, rate.max(MIN_REPEAT_RATE), spawn_repeat::<T>));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    use crate::mock::MockTransport;
+
+    fn keyboard() -> (MockTransport, Keyboard<MockTransport>) {
+        let mock = MockTransport::new();
+        let config = DeviceConfig { name: "keyboard".to_string(), ..DeviceConfig::default() };
+        let keyboard = Keyboard::create(Device::with_transport(mock.clone()), &config).unwrap();
+        (mock, keyboard)
+    }
+
+    fn reports(mock: &MockTransport) -> Vec<Vec<u8>> {
+        mock.written()[1..].iter().map(|event| event[6..].to_vec()).collect()
+    }
+
+    #[test]
+    fn press_release() {
+        let (mock, mut keyboard) = keyboard();
+        keyboard.press(0xe1).unwrap();
+        keyboard.press(0x04).unwrap();
+        keyboard.press(0x04).unwrap();
+        keyboard.press(0x05).unwrap();
+        keyboard.release(0x04).unwrap();
+        keyboard.release(0xe1).unwrap();
+        keyboard.tap(0x06).unwrap();
+        assert!(keyboard.is_pressed(0x05));
+        assert!(!keyboard.is_pressed(0x04));
+
+        assert_eq!(
+            reports(&mock),
+            [
+                [0x02, 0, 0x00, 0, 0, 0, 0, 0],
+                [0x02, 0, 0x04, 0, 0, 0, 0, 0],
+                [0x02, 0, 0x04, 0x05, 0, 0, 0, 0],
+                [0x02, 0, 0x05, 0, 0, 0, 0, 0],
+                [0x00, 0, 0x05, 0, 0, 0, 0, 0],
+                [0x00, 0, 0x05, 0x06, 0, 0, 0, 0],
+                [0x00, 0, 0x05, 0, 0, 0, 0, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn rollover() {
+        let (mock, mut keyboard) = keyboard();
+        for keycode in 0x04..0x0a {
+            keyboard.press(keycode).unwrap();
+        }
+        let written = mock.written().len();
+        match keyboard.press(0x0a) {
+            Err(UhidError::KeyRollover) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(mock.written().len(), written);
+
+        /* modifiers don't take a slot */
+        keyboard.press(0xe0).unwrap();
+        keyboard.release_all().unwrap();
+        keyboard.press(0x0a).unwrap();
+        assert_eq!(reports(&mock).last().unwrap(), &[0x00, 0, 0x0a, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn leds() {
+        let (mock, mut keyboard) = keyboard();
+        let event = Event::Output { data: vec![0x03], rtype: ReportType::Output };
+        assert!(keyboard.handle_event(&event).unwrap());
+        assert_eq!(keyboard.leds(), KeyboardLeds::NUM_LOCK | KeyboardLeds::CAPS_LOCK);
+
+        let event = Event::SetReport { id: 4, rnum: 0, rtype: ReportType::Output, data: vec![0x04] };
+        assert!(keyboard.handle_event(&event).unwrap());
+        assert_eq!(keyboard.leds(), KeyboardLeds::SCROLL_LOCK);
+        assert_eq!(mock.set_report_replies(), [crate::mock::SetReportReply { id: 4, err: 0 }]);
+
+        assert!(!keyboard.handle_event(&Event::Open).unwrap());
+    }
+
+    #[test]
+    fn repeat() {
+        let (mock, mut keyboard) = keyboard();
+        let press = [0, 0, 0x04, 0, 0, 0, 0, 0];
+
+        /* nothing before the delay */
+        keyboard.set_repeat(Duration::from_secs(10), Duration::from_millis(1));
+        keyboard.press(0x04).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        keyboard.release(0x04).unwrap();
+        assert_eq!(reports(&mock), [press, [0; 8]]);
+
+        let (delay, rate) = (Duration::from_millis(20), Duration::from_millis(10));
+        keyboard.set_repeat(delay, rate);
+        let start = Instant::now();
+        keyboard.press(0x04).unwrap();
+        while reports(&mock).len() < 2 + 1 + 3 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        keyboard.release(0x04).unwrap();
+        let elapsed = start.elapsed();
+
+        let reports = reports(&mock)[2..].to_vec();
+        let repeats = reports.len() - 2;
+        /* however late the thread runs, it can't get ahead of its deadlines */
+        let max = ((elapsed - delay).as_nanos() / rate.as_nanos() + 1) as usize;
+        assert!(repeats <= max, "{} repeats in {:?}", repeats, elapsed);
+        assert!(reports[..reports.len() - 1].iter().all(|report| report == &press));
+        assert_eq!(reports.last().unwrap(), &[0; 8]);
+
+        /* stopped on release, modifiers don't repeat */
+        let written = mock.written().len();
+        keyboard.press(0xe0).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(mock.written().len(), written + 1);
+
+        keyboard.clear_repeat().unwrap();
+        keyboard.press(0x05).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(mock.written().len(), written + 2);
+        keyboard.into_device().unwrap();
+    }
+
+    #[test]
+    fn repeat_rate_floor() {
+        let (_, mut keyboard) = keyboard();
+        keyboard.set_repeat(Duration::ZERO, Duration::ZERO);
+        assert_eq!(keyboard.repeat.map(|(_, rate, _)| rate), Some(MIN_REPEAT_RATE));
+        keyboard.set_repeat(Duration::ZERO, Duration::from_millis(30));
+        assert_eq!(keyboard.repeat.map(|(_, rate, _)| rate), Some(Duration::from_millis(30)));
+        keyboard.into_device().unwrap();
+    }
+
+    #[test]
+    fn repeat_error() {
+        let (mock, mut keyboard) = keyboard();
+        keyboard.set_repeat(Duration::ZERO, Duration::from_millis(1));
+        /* the press goes through, the first repeat fails */
+        mock.fail_write_after(1, io::Error::from_raw_os_error(libc::EIO));
+        keyboard.press(0x04).unwrap();
+        while !keyboard.repeat_timer.as_ref().unwrap().thread.is_finished() {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        match keyboard.press(0x05) {
+            Err(UhidError::Input(e)) => assert_eq!(e.raw_os_error(), Some(libc::EIO)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        /* the host saw the key the keyboard holds */
+        assert!(keyboard.is_pressed(0x05));
+        assert_eq!(reports(&mock).last().unwrap(), &[0, 0, 0x04, 0x05, 0, 0, 0, 0]);
+        keyboard.into_device().unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: MIT
+
+//! Mapping between characters and keyboard page usages.
+//!
+//! Only the US layout is covered, and only the printable ASCII characters plus `\n`, `\t` and
+//! `\x08` (backspace). The host applies its own layout to the key codes, so text typed on a
+//! machine set up with another layout comes out differently. Anything else has to be sent as raw
+//! key codes with [`Device::type_keys`](crate::Device::type_keys).
+
+use crate::presets::KeyEvent;
+use crate::UhidError;
+
+/// Left Shift in the modifier bitmap of a keyboard report.
+pub const LEFT_SHIFT: u8 = 1 << 1;
+
+/// Returns the keyboard usage typing `c` on a US keyboard, and whether Shift must be held.
+pub fn char_to_keycode(c: char) -> Option<(u8, bool)> {
+    let key = match c {
+        'a'..='z' => (0x04 + (c as u8 - b'a'), false),
+        'A'..='Z' => (0x04 + (c as u8 - b'A'), true),
+        '1'..='9' => (0x1e + (c as u8 - b'1'), false),
+        '0' => (0x27, false),
+        '!' => (0x1e, true),
+        '@' => (0x1f, true),
+        '#' => (0x20, true),
+        '$' => (0x21, true),
+        '%' => (0x22, true),
+        '^' => (0x23, true),
+        '&' => (0x24, true),
+        '*' => (0x25, true),
+        '(' => (0x26, true),
+        ')' => (0x27, true),
+        '\n' => (0x28, false),
+        '\x08' => (0x2a, false),
+        '\t' => (0x2b, false),
+        ' ' => (0x2c, false),
+        '-' => (0x2d, false),
+        '_' => (0x2d, true),
+        '=' => (0x2e, false),
+        '+' => (0x2e, true),
+        '[' => (0x2f, false),
+        '{' => (0x2f, true),
+        ']' => (0x30, false),
+        '}' => (0x30, true),
+        '\\' => (0x31, false),
+        '|' => (0x31, true),
+        ';' => (0x33, false),
+        ':' => (0x33, true),
+        '\'' => (0x34, false),
+        '"' => (0x34, true),
+        '`' => (0x35, false),
+        '~' => (0x35, true),
+        ',' => (0x36, false),
+        '<' => (0x36, true),
+        '.' => (0x37, false),
+        '>' => (0x37, true),
+        '/' => (0x38, false),
+        '?' => (0x38, true),
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Like [`char_to_keycode`], as a [`KeyEvent`] with Shift in its modifiers if needed.
+pub fn char_to_key_event(c: char) -> Option<KeyEvent> {
+    let (keycode, shift) = char_to_keycode(c)?;
+    Some(KeyEvent { keycode, modifiers: if shift { LEFT_SHIFT } else { 0 } })
+}
+
+/// Maps every character of `text` with [`char_to_key_event`], failing with
+/// [`UhidError::UnmappableChar`] on the first one that can't be typed.
+pub fn key_events(text: &str) -> Result<Vec<KeyEvent>, UhidError> {
+    text.chars().map(|c| char_to_key_event(c).ok_or(UhidError::UnmappableChar(c))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii() {
+        assert_eq!(char_to_keycode('a'), Some((0x04, false)));
+        assert_eq!(char_to_keycode('A'), Some((0x04, true)));
+        assert_eq!(char_to_keycode('z'), Some((0x1d, false)));
+        assert_eq!(char_to_keycode('1'), Some((0x1e, false)));
+        assert_eq!(char_to_keycode('!'), Some((0x1e, true)));
+        assert_eq!(char_to_keycode('0'), Some((0x27, false)));
+        assert_eq!(char_to_keycode('?'), Some((0x38, true)));
+        assert_eq!(char_to_keycode('\n'), Some((0x28, false)));
+
+        assert_eq!(char_to_keycode('é'), None);
+        assert_eq!(char_to_keycode('\x7f'), None);
+        assert!((' '..='~').all(|c| char_to_keycode(c).is_some()));
+    }
+
+    #[test]
+    fn key_event() {
+        assert_eq!(char_to_key_event('Q'), Some(KeyEvent { keycode: 0x14, modifiers: LEFT_SHIFT }));
+        assert_eq!(char_to_key_event('q'), Some(KeyEvent { keycode: 0x14, modifiers: 0 }));
+    }
+}
@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: MIT
+
+//! Ready-made configurations for a few recognizable devices.
+//!
+//! The vendor and product IDs, names and buses match real devices, but the report descriptors are
+//! the simplified ones from [`presets`], not dumps of the real hardware. That is enough for the
+//! generic HID drivers the kernel binds to these IDs; host software that recognizes the IDs and
+//! expects the vendor's own protocol will not find it.
+//!
+//! The gamepad is the exception: real Xbox pads don't speak HID over USB, the kernel drives them
+//! with xpad, so it uses the pid.codes test IDs instead, which no driver but hid-generic claims.
+
+use crate::{presets, Bus, DeviceConfig, UhidError};
+
+struct KnownDevice {
+    key: &'static str,
+    name: &'static str,
+    bus: Bus,
+    vendor: u32,
+    product: u32,
+    rdesc: fn() -> Vec<u8>,
+}
+
+const KNOWN_DEVICES: &[KnownDevice] = &[
+    KnownDevice {
+        key: "logitech-m105",
+        name: "Logitech USB Optical Mouse",
+        bus: Bus::USB,
+        vendor: 0x046d,
+        product: 0xc077,
+        rdesc: presets::boot_mouse,
+    },
+    KnownDevice {
+        key: "dell-kb216",
+        name: "Dell KB216 Wired Keyboard",
+        bus: Bus::USB,
+        vendor: 0x413c,
+        product: 0x2113,
+        rdesc: presets::boot_keyboard,
+    },
+    KnownDevice {
+        key: "xbox-style-pad",
+        name: "Xbox-style HID gamepad",
+        bus: Bus::USB,
+        /* pid.codes test VID and PID, not Microsoft's 045e:028e which is handled by xpad */
+        vendor: 0x1209,
+        product: 0x0001,
+        rdesc: presets::gamepad_rumble,
+    },
+];
+
+/// Names accepted by [`config`].
+pub fn names() -> impl Iterator<Item = &'static str> {
+    KNOWN_DEVICES.iter().map(|device| device.key)
+}
+
+/// Returns the configuration of the device called `name`, see [`names`].
+pub fn config(name: &str) -> Result<DeviceConfig, UhidError> {
+    match KNOWN_DEVICES.iter().find(|device| device.key == name) {
+        Some(device) => Ok(DeviceConfig {
+            name: device.name.to_string(),
+            rdesc: (device.rdesc)(),
+            bus: device.bus,
+            vendor: device.vendor,
+            product: device.product,
+            ..DeviceConfig::default()
+        }),
+        None => Err(UhidError::UnknownDevice(name.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor;
+
+    #[test]
+    fn known() {
+        let keyboard = config("dell-kb216").unwrap();
+        assert_eq!((keyboard.vendor, keyboard.product), (0x413c, 0x2113));
+        assert_eq!(keyboard.rdesc, presets::boot_keyboard());
+
+        let pad = config("xbox-style-pad").unwrap();
+        assert_eq!(descriptor::descriptor_class(&pad.rdesc).unwrap(), descriptor::DeviceClass::Gamepad);
+
+        for name in names() {
+            assert!(descriptor::parse_descriptor(&config(name).unwrap().rdesc).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn unknown() {
+        match config("no-such-device") {
+            Err(UhidError::UnknownDevice(name)) => assert_eq!(name, "no-such-device"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: MIT
+
+//! Latency tracking, see [`Device::latency_stats`](crate::Device::latency_stats).
+//!
+//! Only built with the `latency-stats` feature, without it the device takes no timestamps.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Number of recent samples kept by [`LatencySamples`].
+pub const RECENT_SAMPLES: usize = 64;
+
+/// Latency samples of one measurement point.
+///
+/// The [`RECENT_SAMPLES`] most recent samples are kept as they are, the count, minimum, maximum
+/// and mean cover every sample since the device was opened.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LatencySamples {
+    recent: VecDeque<Duration>,
+    count: u64,
+    total: Duration,
+    min: Option<Duration>,
+    max: Duration,
+}
+
+impl LatencySamples {
+    pub(crate) fn record(&mut self, sample: Duration) {
+        if self.recent.len() == RECENT_SAMPLES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(sample);
+        self.count += 1;
+        self.total = self.total.saturating_add(sample);
+        self.min = Some(self.min.map_or(sample, |min| min.min(sample)));
+        self.max = self.max.max(sample);
+    }
+
+    /// The most recent samples, oldest first.
+    pub fn recent(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.recent.iter().copied()
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.min.map(|_| self.max)
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        match self.count {
+            0 => None,
+            /* Duration only divides by u32, go through nanoseconds to take any count */
+            count => Some(Duration::from_nanos((self.total.as_nanos() / count as u128) as u64)),
+        }
+    }
+}
+
+/// Latencies measured by a [`Device`](crate::Device).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LatencyStats {
+    /// Time spent in [`Device::read_event`](crate::Device::read_event), from the call until the
+    /// event is returned. This is mostly the wait for the kernel to queue an event, calls that
+    /// fail are not recorded.
+    pub read: LatencySamples,
+    /// Time from [`Device::read_event`](crate::Device::read_event) returning an [`Event::Output`]
+    /// to the next input report being written to the kernel by
+    /// [`Device::input`](crate::Device::input), the time the application took to answer the
+    /// output report. Only the first input report after each output report counts.
+    ///
+    /// [`Event::Output`]: crate::Event::Output
+    pub turnaround: LatencySamples,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples() {
+        let mut samples = LatencySamples::default();
+        assert_eq!((samples.min(), samples.max(), samples.mean()), (None, None, None));
+
+        for ms in 1..=RECENT_SAMPLES as u64 + 2 {
+            samples.record(Duration::from_millis(ms));
+        }
+        assert_eq!(samples.count(), RECENT_SAMPLES as u64 + 2);
+        assert_eq!(samples.min(), Some(Duration::from_millis(1)));
+        assert_eq!(samples.max(), Some(Duration::from_millis(66)));
+        assert_eq!(samples.mean(), Some(Duration::from_micros(33_500)));
+        assert_eq!(samples.recent().count(), RECENT_SAMPLES);
+        assert_eq!(samples.recent().next(), Some(Duration::from_millis(3)));
+    }
+}
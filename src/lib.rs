@@ -1,43 +1,191 @@
 // SPDX-License-Identifier: MIT
 
-use std::convert::TryInto;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
 
-use std::fs::{File, OpenOptions};
-use std::os::unix::io::RawFd;
-use std::io::Write;
-
-use serde::{Serialize, Deserialize};
+use serde::Serialize;
 use serde_big_array::big_array;
 
+mod builder;
+mod config_file;
+pub mod descriptor;
+mod error;
+mod event;
+mod keyboard;
+pub mod keymap;
+pub mod known_devices;
+#[cfg(feature = "latency-stats")]
+pub mod latency;
+pub mod mock;
+mod mouse;
+pub mod presets;
+pub mod report;
+pub mod usages;
+
+pub use builder::DeviceBuilder;
+pub use error::UhidError;
+pub use event::{event_buffer_size, event_type, BlockingEvents, Event, NonblockingEvents, StartFlags, UHID_EVENT_SIZE};
+pub use keyboard::{Keyboard, KeyboardLeds, MAX_KEYS, MIN_REPEAT_RATE};
+pub use mouse::{Mouse, MouseButton, MouseMode, ABSOLUTE_MAX, HIRES_WHEEL_MULTIPLIER};
+
 big_array!{BigArray;}
 
 const HID_MAX_DESCRIPTOR_SIZE: usize = 4096;
 
-enum Bus {
-    PCI,
-    ISAPNP,
-    USB,
-    HIL,
-    BLUETOOTH,
-    VIRTUAL,
+/// Maximum report descriptor length accepted by the kernel.
+///
+/// This is `HID_MAX_DESCRIPTOR_SIZE` from `<linux/hid.h>`, which also sizes the `rd_data` field of
+/// `struct uhid_create2_req`, so it is part of the UHID ABI and cannot change without breaking it.
+pub const MAX_DESCRIPTOR_SIZE: usize = HID_MAX_DESCRIPTOR_SIZE;
+
+/// How often [`Device::run_while`] and [`Device::spawn_event_reader_while`] check their flag
+/// while no event arrives, which bounds how long they take to notice it was cleared.
+pub const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Highest country code defined by the HID specification (`bCountryCode`, 35 is Turkish-F).
+pub const MAX_COUNTRY: u32 = 35;
+
+/// Bus type reported to the kernel, the `BUS_*` values from `<linux/input.h>`.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Bus {
+    PCI = 0x01,
+    ISAPNP = 0x02,
+    #[default]
+    USB = 0x03,
+    HIL = 0x04,
+    BLUETOOTH = 0x05,
+    VIRTUAL = 0x06,
+}
+
+impl Bus {
+    const ALL: [Bus; 6] = [Bus::PCI, Bus::ISAPNP, Bus::USB, Bus::HIL, Bus::BLUETOOTH, Bus::VIRTUAL];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Bus::PCI => "pci",
+            Bus::ISAPNP => "isapnp",
+            Bus::USB => "usb",
+            Bus::HIL => "hil",
+            Bus::BLUETOOTH => "bluetooth",
+            Bus::VIRTUAL => "virtual",
+        }
+    }
+}
+
+impl fmt::Display for Bus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Bus {
+    type Err = UhidError;
+
+    /// Parses the lowercase name printed by `Display`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Self::ALL.iter().find(|bus| bus.name().eq_ignore_ascii_case(s)) {
+            Some(bus) => Ok(*bus),
+            None => Err(UhidError::UnknownBus(s.to_string())),
+        }
+    }
+}
+
+/// Vendor and product ID pair, written `vvvv:pppp` in hex like `lsusb` does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DeviceId {
+    pub vendor: u16,
+    pub product: u16,
+}
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04x}:{:04x}", self.vendor, self.product)
+    }
+}
+
+impl FromStr for DeviceId {
+    type Err = UhidError;
+
+    /// Parses `vvvv:pppp`, both IDs in hex and optionally prefixed with `0x`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |id: &str| {
+            let hex = id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")).unwrap_or(id);
+            if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            u16::from_str_radix(hex, 16).ok()
+        };
+        let mut ids = s.splitn(2, ':');
+        match (ids.next().and_then(parse), ids.next().and_then(parse)) {
+            (Some(vendor), Some(product)) => Ok(DeviceId { vendor, product }),
+            _ => Err(UhidError::InvalidId(s.to_string())),
+        }
+    }
+}
+
+/// Kind of a HID report, the `UHID_*_REPORT` values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportType {
+    Feature = 0,
+    Output = 1,
+    Input = 2,
 }
 
+/// Raw event type, the values of `enum uhid_event_type`.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum EventType {
-    __LegacyCreate,
-    Destroy,
-    Start,
-    Stop,
-    Open,
-    Close,
-    Output,
-    __LoegacyOutputEv,
-    __LegacyInput,
-    GetReport,
-    GetReportReply,
-    Create2,
-    Input2,
-    SetReport,
-    SetReportReply,
+    __LegacyCreate = 0,
+    Destroy = 1,
+    Start = 2,
+    Stop = 3,
+    Open = 4,
+    Close = 5,
+    Output = 6,
+    __LegacyOutputEv = 7,
+    __LegacyInput = 8,
+    GetReport = 9,
+    GetReportReply = 10,
+    Create2 = 11,
+    Input2 = 12,
+    SetReport = 13,
+    SetReportReply = 14,
+}
+
+impl EventType {
+    const ALL: [EventType; 15] = [
+        EventType::__LegacyCreate,
+        EventType::Destroy,
+        EventType::Start,
+        EventType::Stop,
+        EventType::Open,
+        EventType::Close,
+        EventType::Output,
+        EventType::__LegacyOutputEv,
+        EventType::__LegacyInput,
+        EventType::GetReport,
+        EventType::GetReportReply,
+        EventType::Create2,
+        EventType::Input2,
+        EventType::SetReport,
+        EventType::SetReportReply,
+    ];
+
+    fn from_u32(value: u32) -> Option<Self> {
+        Self::ALL.iter().find(|&&event_type| event_type as u32 == value).copied()
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -58,151 +206,3275 @@ struct Create2Req {
     rd_data: [u8; HID_MAX_DESCRIPTOR_SIZE],
 }
 
-pub struct Device {
-    uhid_fd: File,
-    created: bool,
+/* the fields have no padding between them, so this is the packed size bincode writes */
+const _: () = assert!(UHID_EVENT_SIZE == 4 + std::mem::size_of::<Create2Req>());
+
+/// Picks a duration in `base - jitter..=base + jitter`, not below zero, from `seed`.
+fn jittered(base: Duration, jitter: Duration, seed: u64) -> Duration {
+    /* splitmix64, which spreads even consecutive seeds well */
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    let random = z ^ (z >> 31);
+
+    let span = jitter.as_nanos().saturating_mul(2).saturating_add(1);
+    let offset = Duration::from_nanos((random as u128 % span).min(u64::MAX as u128) as u64);
+    (base + offset).saturating_sub(jitter)
+}
+
+/// Copies `s` into a zero-padded `N`-byte string field of a create request.
+///
+/// The kernel keeps the last byte of the field for the NUL, so `s` may only take `N - 1` bytes.
+/// Fails with [`UhidError::StringTooLong`] for `field` if `s` doesn't fit.
+fn str_to_fixed<const N: usize>(field: &'static str, s: &str) -> Result<[u8; N], UhidError> {
+    let max = N - 1;
+    if s.len() > max {
+        return Err(UhidError::StringTooLong { field, len: s.len(), max });
+    }
+    let mut fixed = [0; N];
+    fixed[..s.len()].copy_from_slice(s.as_bytes());
+    Ok(fixed)
+}
+
+/// Builds the `UHID_CREATE` equivalent of `req`, which holds `rdesc`.
+///
+/// Unlike Create2, `struct uhid_create_req` points to the descriptor instead of holding it. The
+/// kernel copies it during the write, so the event must be written while `rdesc` is borrowed.
+fn legacy_create_event(req: &Create2Req, rdesc: &[u8]) -> Vec<u8> {
+    let mut event = Vec::with_capacity(LEGACY_CREATE_SIZE);
+    event.extend_from_slice(&(EventType::__LegacyCreate as u32).to_le_bytes());
+    event.extend_from_slice(&req.name);
+    event.extend_from_slice(&req.phys);
+    event.extend_from_slice(&req.uniq);
+    event.extend_from_slice(&(rdesc.as_ptr() as usize).to_ne_bytes());
+    event.extend_from_slice(&req.rd_size.to_le_bytes());
+    event.extend_from_slice(&req.bus.to_le_bytes());
+    for value in &[req.vendor, req.product, req.version, req.country] {
+        event.extend_from_slice(&value.to_le_bytes());
+    }
+    event
+}
+
+/// Size of a `UHID_CREATE` event: type, strings, descriptor pointer, then the same fields as Create2.
+const LEGACY_CREATE_SIZE: usize = 4 + 128 + 64 + 64 + std::mem::size_of::<usize>() + 2 + 2 + 4 * 4;
+
+/// Checks the layout of an event the device is about to write, for the `debug_assert!` in
+/// `write_event()`. Only used in debug builds, to catch encoding mistakes before the kernel does.
+fn is_well_formed(event: &[u8]) -> bool {
+    let u16_at = |offset: usize| event.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize);
+    let event_type = match event.get(..4) {
+        Some(raw) => EventType::from_u32(u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]])),
+        None => return false,
+    };
+    match event_type {
+        Some(EventType::Create2) => {
+            event.len() == UHID_EVENT_SIZE && u16_at(260).is_some_and(|size| size <= HID_MAX_DESCRIPTOR_SIZE)
+        }
+        Some(EventType::__LegacyCreate) => event.len() == LEGACY_CREATE_SIZE,
+        Some(EventType::Destroy) => event.len() == 4,
+        /* type, size, data */
+        Some(EventType::Input2) => u16_at(4).is_some_and(|size| event.len() == 6 + size),
+        /* type, data padded to UHID_DATA_MAX, size */
+        Some(EventType::__LegacyInput) => {
+            event.len() == 6 + HID_MAX_DESCRIPTOR_SIZE
+                && u16_at(4 + HID_MAX_DESCRIPTOR_SIZE).is_some_and(|size| size <= HID_MAX_DESCRIPTOR_SIZE)
+        }
+        /* type, id, err, size, data */
+        Some(EventType::GetReportReply) => u16_at(10).is_some_and(|size| event.len() == 12 + size),
+        /* type, id, err */
+        Some(EventType::SetReportReply) => event.len() == 10,
+        _ => false,
+    }
+}
+
+/// Appends `value` to `buf` in the kernel's layout: little-endian, fixed-size integers, no padding.
+fn serialize_into<S: Serialize + ?Sized>(buf: &mut Vec<u8>, value: &S) -> Result<(), UhidError> {
+    bincode::serialize_into(buf, value).map_err(UhidError::Serialize)
+}
+
+/// Byte stream the UHID events are written to and read from.
+///
+/// This is `/dev/uhid` in normal operation, but tests can swap in a [`mock::MockTransport`].
+pub trait Transport: Read + Write {
+    /// Blocks until a write would not fail with `WouldBlock`.
+    fn wait_writable(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Waits up to `timeout` (forever if `None`) for an event to read, returns whether there is one.
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> io::Result<bool>;
+}
+
+/// Milliseconds left until `deadline` for poll and epoll_wait, `-1` to wait forever.
+fn timeout_ms(deadline: Option<Instant>) -> libc::c_int {
+    match deadline {
+        /* round up so we never wake up just before the deadline */
+        Some(deadline) => {
+            let left = deadline.saturating_duration_since(Instant::now());
+            (left.as_nanos().div_ceil(1_000_000)).min(libc::c_int::MAX as u128) as libc::c_int
+        }
+        None => -1,
+    }
+}
+
+fn poll_fd(fd: RawFd, events: libc::c_short, timeout: Option<Duration>) -> io::Result<bool> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut pollfd = libc::pollfd {
+        fd,
+        events,
+        revents: 0,
+    };
+    loop {
+        match unsafe { libc::poll(&mut pollfd, 1, timeout_ms(deadline)) } {
+            -1 => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+            n => return Ok(n > 0),
+        }
+    }
+}
+
+impl Transport for File {
+    fn wait_writable(&mut self) -> io::Result<()> {
+        poll_fd(self.as_raw_fd(), libc::POLLOUT, None).map(|_| ())
+    }
+
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+        poll_fd(self.as_raw_fd(), libc::POLLIN, timeout)
+    }
+}
+
+/// What [`Device::input`] does when the kernel can't take a report right away.
+///
+/// This only happens on a nonblocking file descriptor, where the write fails with `EAGAIN`.
+/// `Fail` keeps `input()` from ever blocking but leaves it to the caller to retry or drop the
+/// report, `Block` never loses a report but stalls the caller until the kernel catches up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Return [`UhidError::QueueFull`].
+    #[default]
+    Fail,
+    /// Wait until the file descriptor is writable and retry.
+    Block,
+}
+
+/// Transport that logs the events instead of sending them to the kernel.
+///
+/// Every event is written to the sink as one line with its type, size and bytes in hex, such as
+/// `uhid dry-run: Destroy (4 bytes): 01 00 00 00`. The zero padding after the descriptor of a
+/// create event, or after the report of a legacy input event, is shown as `[N zero bytes]` instead.
+/// The sink is stderr by default. Writes succeed
+/// as long as the sink takes the line, and reads always fail with `WouldBlock`, as no kernel is
+/// there to answer.
+pub struct DryRun {
+    sink: Box<dyn Write + Send>,
+}
+
+impl DryRun {
+    /// Logs the events to `sink`.
+    pub fn new(sink: impl Write + Send + 'static) -> Self {
+        DryRun { sink: Box::new(sink) }
+    }
+
+    /// Range of the zero padding in `event`, which the log skips: the unused part of the descriptor
+    /// in Create2, and of the report in a legacy Input.
+    fn padding(event_type: Option<EventType>, event: &[u8]) -> Option<Range<usize>> {
+        let u16_at = |offset: usize| event.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize);
+        let (start, end) = match event_type? {
+            /* type, name, phys, uniq, rd_size, bus, vendor, product, version, country, rd_data */
+            EventType::Create2 => (280 + u16_at(260)?, UHID_EVENT_SIZE),
+            /* type, data padded to UHID_DATA_MAX, size */
+            EventType::__LegacyInput => (4 + u16_at(4 + HID_MAX_DESCRIPTOR_SIZE)?, 4 + HID_MAX_DESCRIPTOR_SIZE),
+            _ => return None,
+        };
+        (start < end && end <= event.len()).then_some(start..end)
+    }
+}
+
+impl Default for DryRun {
+    fn default() -> Self {
+        Self::new(io::stderr())
+    }
+}
+
+impl Read for DryRun {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::WouldBlock, "dry-run device has no events"))
+    }
+}
+
+impl Write for DryRun {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let event_type = buf
+            .get(..4)
+            .map(|raw| u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]))
+            .and_then(EventType::from_u32);
+        let name = match event_type {
+            Some(EventType::__LegacyCreate) => "Create".to_string(),
+            Some(EventType::__LegacyInput) => "Input".to_string(),
+            Some(event_type) => format!("{:?}", event_type),
+            None => "unknown event".to_string(),
+        };
+        let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ");
+        let bytes = match Self::padding(event_type, buf) {
+            Some(padding) => {
+                let mut bytes = format!("{} [{} zero bytes]", hex(&buf[..padding.start]), padding.len());
+                if padding.end < buf.len() {
+                    bytes = format!("{} {}", bytes, hex(&buf[padding.end..]));
+                }
+                bytes
+            }
+            None => hex(buf),
+        };
+        writeln!(self.sink, "uhid dry-run: {} ({} bytes): {}", name, buf.len(), bytes)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()
+    }
+}
+
+impl Transport for DryRun {
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+        match timeout {
+            Some(timeout) => {
+                thread::sleep(timeout);
+                Ok(false)
+            }
+            None => Err(io::Error::new(io::ErrorKind::WouldBlock, "dry-run device has no events")),
+        }
+    }
+}
+
+/// Parameters the device is created with.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviceConfig {
+    pub name: String,
+    /// Physical location of the device, see [`DeviceBuilder::phys`].
+    pub phys: String,
+    /// Unique identifier of the device, see [`DeviceBuilder::serial`].
+    pub uniq: String,
+    pub rdesc: Vec<u8>,
+    pub bus: Bus,
+    pub vendor: u32,
+    pub product: u32,
+    pub version: u32,
+    pub country: u32,
+    /// See [`DeviceBuilder::raw_flags`].
+    pub raw_flags: u64,
+    /// See [`DeviceBuilder::creation_mode`].
+    pub creation_mode: CreationMode,
+}
+
+impl DeviceConfig {
+    /// Pattern matching the `modalias` the kernel will give the device, for udev rules and
+    /// `MODULE_ALIAS` style matching, such as `hid:b0003g*v0000046Dp0000C077`.
+    ///
+    /// The kernel's modalias also holds the device group, which it picks by scanning the report
+    /// descriptor (generic, multitouch, sensor hub, ...), so it is left as a wildcard here. Once
+    /// the device exists, [`Device::modalias`] returns the real one.
+    pub fn modalias_pattern(&self) -> String {
+        format!("hid:b{:04X}g*v{:08X}p{:08X}", self.bus as u16, self.vendor, self.product)
+    }
+}
+
+/// Event used to create the device, see [`DeviceBuilder::creation_mode`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CreationMode {
+    /// `UHID_CREATE2`, Linux 3.18 and newer.
+    #[default]
+    Create2,
+    /// `UHID_CREATE` and `UHID_INPUT`, the only events of Linux 3.6 to 3.17.
+    Legacy,
+    /// `UHID_CREATE2`, falling back to the legacy events if the kernel doesn't know it.
+    Auto,
+}
+
+/// Lifecycle state of a [`Device`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceState {
+    /// `create()` has not been called yet.
+    Uninitialized,
+    /// The Create2 event was sent.
+    Created,
+    /// The device was destroyed.
+    Destroyed,
+}
+
+/// Counters of the traffic through a [`Device`], see [`Device::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub inputs_sent: u64,
+    /// Report bytes sent in input reports.
+    pub bytes_sent: u64,
+    pub outputs_received: u64,
+    pub get_reports: u64,
+    pub set_reports: u64,
+}
+
+/// Handler of the output report with one report ID, see [`Device::on_output`].
+pub type OutputHandler = Box<dyn FnMut(&[u8]) + Send>;
+
+/// Handle to a UHID device.
+///
+/// Every event the device sends has a typed method that takes care of its framing:
+///
+/// | Event                   | Method                                       | Payload after the type        |
+/// |-------------------------|----------------------------------------------|-------------------------------|
+/// | `UHID_CREATE2`          | [`create_with`](Self::create_with)           | `struct uhid_create2_req`     |
+/// | `UHID_CREATE` (legacy)  | [`create_with`](Self::create_with)           | `struct uhid_create_req`      |
+/// | `UHID_DESTROY`          | [`destroy`](Self::destroy)                   | none                          |
+/// | `UHID_INPUT2`           | [`input`](Self::input)                       | `u16` size, data              |
+/// | `UHID_INPUT` (legacy)   | [`input`](Self::input)                       | data in 4096 bytes, `u16` size |
+/// | `UHID_GET_REPORT_REPLY` | [`get_report_reply`](Self::get_report_reply) | `u32` id, `u16` err, `u16` size, data |
+/// | `UHID_SET_REPORT_REPLY` | [`set_report_reply`](Self::set_report_reply) | `u32` id, `u16` err           |
+///
+/// All fields are little-endian.
+pub struct Device<T = File> {
+    uhid_fd: T,
+    state: DeviceState,
+    config: DeviceConfig,
+    backpressure: Backpressure,
+    stats: Stats,
+    open_count: u32,
+    report_lengths: HashMap<Option<u8>, usize>,
+    start_flags: Option<StartFlags>,
+    feature_reports: HashMap<u8, Vec<u8>>,
+    /* request that run() is waiting on the handler to answer */
+    unanswered: Option<u32>,
+    auto_reply: bool,
+    output_handlers: HashMap<u8, OutputHandler>,
+    /* created with UHID_CREATE, so input goes out as UHID_INPUT */
+    legacy: bool,
+    /* reused by every read, events copy what they need out of it */
+    read_buf: Box<[u8]>,
+    #[cfg(feature = "latency-stats")]
+    latency: latency::LatencyStats,
+    /* when read_event() returned the last Output, until the next input report */
+    #[cfg(feature = "latency-stats")]
+    output_read_at: Option<Instant>,
 }
 
 impl Device {
-    pub fn new() -> Result<Self, String> {
-        Ok(Device {
-            uhid_fd: match OpenOptions::new().read(true).write(true).open("/dev/uhid") {
-                Ok(f) => f,
-                Err(e) => return Err(format!("failed to open the UHID file descriptor ({})", e)),
-            },
-            created: false,
-        })
+    pub fn new() -> Result<Self, UhidError> {
+        Self::open(0)
+    }
+
+    /// Opens `/dev/uhid` with `O_NONBLOCK` already set, so there is no window where the file
+    /// descriptor blocks, unlike calling [`set_nonblocking`](Self::set_nonblocking) afterwards.
+    pub fn new_nonblocking() -> Result<Self, UhidError> {
+        Self::open(libc::O_NONBLOCK)
+    }
+
+    fn open(flags: libc::c_int) -> Result<Self, UhidError> {
+        match OpenOptions::new().read(true).write(true).custom_flags(flags).open("/dev/uhid") {
+            Ok(f) => Ok(Self::with_transport(f)),
+            Err(e) => Err(UhidError::Open(e)),
+        }
+    }
+
+    /// Creates `n` devices from `base_config`, which only differ by their `uniq` field.
+    ///
+    /// Device `i` gets `uniq` set to `{base uniq}-{i}`, or just `{i}` if the base has none. Every
+    /// device has its own `/dev/uhid` handle, so this uses `n` file descriptors; mind
+    /// `RLIMIT_NOFILE` (often 1024) for large `n`. If one of them fails, the ones created so far
+    /// are closed again, which removes them.
+    pub fn create_n(n: usize, base_config: &DeviceConfig) -> Result<Vec<Device>, UhidError> {
+        let mut devices = Vec::with_capacity(n);
+        for index in 0..n {
+            let mut dev = Device::new()?;
+            dev.create_with(&indexed_config(base_config, index))?;
+            devices.push(dev);
+        }
+        Ok(devices)
+    }
+
+    /// Wraps an already open UHID file descriptor.
+    pub fn from_fd(fd: OwnedFd) -> Self {
+        Self::with_transport(File::from(fd))
+    }
+
+    /// Returns a second handle to the same UHID device, for example to read events on one thread
+    /// while sending input on another.
+    ///
+    /// The file descriptor is duplicated, so both handles share its flags such as `O_NONBLOCK`,
+    /// and the device lives until both are dropped. The clone starts with a copy of this handle's
+    /// state, feature reports to answer included, but from then on each tracks it separately:
+    /// only one of them should create and destroy the device, and only the one reading events sees
+    /// Open, Close and Start. The clone starts without [`on_output`](Self::on_output) handlers,
+    /// with zeroed [`stats`](Self::stats) and, with the `latency-stats` feature, no latency
+    /// samples. Every write is a whole event, so input sent from both handles is not interleaved
+    /// within a report.
+    ///
+    /// A handle can't be dropped while a thread is reading through it, so a reader never has its
+    /// file descriptor closed under it. Dropping the other handle doesn't wake a blocked reader,
+    /// the file stays open through the reader's descriptor. Destroying the device through it
+    /// makes the kernel queue a Stop event, which ends [`blocking_events`](Self::blocking_events),
+    /// but only if a driver had started the device, otherwise the read keeps blocking. Readers
+    /// that have to be stoppable should wait with a timeout, with [`wait_for`](Self::wait_for)
+    /// for example. A transport that reaches end of file fails reads with
+    /// [`UhidError::UnexpectedEof`] and writes with [`UhidError::DeviceGone`].
+    pub fn try_clone_handle(&self) -> Result<Device, UhidError> {
+        let file = match self.uhid_fd.try_clone() {
+            Ok(file) => file,
+            Err(e) => return Err(UhidError::Fcntl(e)),
+        };
+        let mut dev = Device::with_transport(file);
+        dev.state = self.state;
+        dev.config = self.config.clone();
+        dev.backpressure = self.backpressure;
+        dev.report_lengths = self.report_lengths.clone();
+        dev.start_flags = self.start_flags;
+        dev.feature_reports = self.feature_reports.clone();
+        dev.open_count = self.open_count;
+        dev.auto_reply = self.auto_reply;
+        dev.legacy = self.legacy;
+        Ok(dev)
     }
 
-    fn event(event_type: EventType, mut data: Option<Vec<u8>>) -> Vec<u8> {
+    fn fd_flags(&self) -> Result<libc::c_int, UhidError> {
+        match unsafe { libc::fcntl(self.uhid_fd.as_raw_fd(), libc::F_GETFL) } {
+            -1 => Err(UhidError::Fcntl(io::Error::last_os_error())),
+            flags => Ok(flags),
+        }
+    }
+
+    /// Number of file descriptors the device owns, always 1: the `/dev/uhid` handle, or the file
+    /// descriptor given to [`from_fd`](Self::from_fd). It is closed when the device is dropped,
+    /// which also makes the kernel remove the device. [`try_clone_handle`](Self::try_clone_handle)
+    /// duplicates it, the clone owns its own.
+    pub fn fd_count(&self) -> usize {
+        1
+    }
+
+    /// Returns whether the file descriptor has `O_NONBLOCK` set.
+    pub fn is_nonblocking(&self) -> Result<bool, UhidError> {
+        Ok(self.fd_flags()? & libc::O_NONBLOCK != 0)
+    }
+
+    /// Sets or clears `O_NONBLOCK` on the file descriptor.
+    pub fn set_nonblocking(&mut self, nb: bool) -> Result<(), UhidError> {
+        let flags = self.fd_flags()?;
+        let flags = if nb {
+            flags | libc::O_NONBLOCK
+        } else {
+            flags & !libc::O_NONBLOCK
+        };
+
+        match unsafe { libc::fcntl(self.uhid_fd.as_raw_fd(), libc::F_SETFL, flags) } {
+            -1 => Err(UhidError::Fcntl(io::Error::last_os_error())),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Driver bound to the device at `device_path` in sysfs, from its `driver` symlink.
+fn driver_name(device_path: &Path) -> Option<String> {
+    let driver = fs::read_link(device_path.join("driver")).ok()?;
+    Some(driver.file_name()?.to_string_lossy().into_owned())
+}
+
+fn indexed_config(base: &DeviceConfig, index: usize) -> DeviceConfig {
+    let uniq = match base.uniq.as_str() {
+        "" => index.to_string(),
+        uniq => format!("{}-{}", uniq, index),
+    };
+    DeviceConfig { uniq, ..base.clone() }
+}
+
+impl Device<DryRun> {
+    /// Creates a device that only prints the events it would send to stderr, for running without
+    /// `/dev/uhid`.
+    pub fn dry_run() -> Self {
+        Self::with_transport(DryRun::default())
+    }
+
+    /// Like [`dry_run`](Self::dry_run), but logs the events to `sink`, see [`DryRun`].
+    pub fn dry_run_to(sink: impl Write + Send + 'static) -> Self {
+        Self::with_transport(DryRun::new(sink))
+    }
+}
+
+impl<T: Transport> Device<T> {
+    pub fn with_transport(transport: T) -> Self {
+        Device {
+            uhid_fd: transport,
+            state: DeviceState::Uninitialized,
+            config: DeviceConfig::default(),
+            backpressure: Backpressure::default(),
+            stats: Stats::default(),
+            open_count: 0,
+            report_lengths: HashMap::new(),
+            start_flags: None,
+            feature_reports: HashMap::new(),
+            unanswered: None,
+            auto_reply: true,
+            output_handlers: HashMap::new(),
+            legacy: false,
+            read_buf: vec![0; UHID_EVENT_SIZE].into_boxed_slice(),
+            #[cfg(feature = "latency-stats")]
+            latency: latency::LatencyStats::default(),
+            #[cfg(feature = "latency-stats")]
+            output_read_at: None,
+        }
+    }
+
+    /// Builds an event from its type, the fixed fields of the request and the trailing report data.
+    fn event<S: Serialize + ?Sized>(event_type: EventType, fields: &S, data: &[u8]) -> Result<Vec<u8>, UhidError> {
         /* build event manually as serde/bincode does not support unions,
            and so doesn't let us make a struct uhid_event */
-        let event_type_id = event_type as u32;
-        let mut event = bincode::serialize::<u32>(&event_type_id).unwrap();
-        match data {
-            Some(mut data_vec) => event.append(&mut data_vec),
-            None => (),
-        };
-        return event;
+        let fields_size = bincode::serialized_size(fields).map_err(UhidError::Serialize)? as usize;
+        /* everything goes in one buffer: uhid has no write_iter, so a vectored write would reach
+           it as one write per iovec, each taken as a separate event */
+        let mut event = Vec::with_capacity(4 + fields_size + data.len());
+        serialize_into(&mut event, &(event_type as u32))?;
+        serialize_into(&mut event, fields)?;
+        event.extend_from_slice(data);
+        Ok(event)
+    }
+
+    fn write_event(&mut self, event: &[u8]) -> Result<(), UhidError> {
+        debug_assert!(
+            is_well_formed(event),
+            "malformed {} byte event: {:02x?}",
+            event.len(),
+            &event[..event.len().min(16)]
+        );
+        /* the kernel consumes a whole event per write, a short write means it was not sent */
+        match self.uhid_fd.write(event) {
+            Ok(n) if n == event.len() => Ok(()),
+            Ok(n) => Err(UhidError::Write(io::Error::new(
+                io::ErrorKind::WriteZero,
+                format!("short write ({} of {} bytes)", n, event.len()),
+            ))),
+            /* the device was torn down under us, don't let further calls reach the kernel */
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe || e.raw_os_error() == Some(libc::ENODEV) => {
+                self.state = DeviceState::Destroyed;
+                Err(UhidError::DeviceGone)
+            }
+            Err(e) => Err(UhidError::Write(e)),
+        }
+    }
+
+    pub fn create(&mut self, vid: u32, pid: u32, name: &str, rdesc: &[u8], bus: Option<Bus>) -> Result<(), UhidError> {
+        self.create_with(&DeviceConfig {
+            name: name.to_string(),
+            rdesc: rdesc.to_vec(),
+            bus: bus.unwrap_or_default(),
+            vendor: vid,
+            product: pid,
+            ..DeviceConfig::default()
+        })
     }
 
-    pub fn create(&mut self, vid: u32, pid: u32, name: &str, rdesc: &[u8], bus: Option<u16>) -> Result<(), String> {
-        if self.created {
-            return Err("device already created".to_string());
+    /// Creates the device described by `config`.
+    ///
+    /// The kernel registers the device asynchronously, after this returns, and drops the input
+    /// reports it receives before a driver is bound to the device instead of queueing them. A
+    /// neutral report sent right away therefore does nothing to bring the device up, and neither
+    /// does sleeping for a fixed time on a loaded system. When the first reports must not be lost,
+    /// use [`create_and_wait`](Self::create_and_wait), which waits for the Start event the kernel
+    /// sends once a driver is bound.
+    ///
+    /// Only the kernel's synchronous checks are reported here, this is fire-and-forget otherwise.
+    /// [`create_confirmed`](Self::create_confirmed) also reports a device that fails to come up.
+    pub fn create_with(&mut self, config: &DeviceConfig) -> Result<(), UhidError> {
+        if self.is_created() {
+            return Err(UhidError::AlreadyCreated);
+        }
+        if config.raw_flags != 0 {
+            return Err(UhidError::UnsupportedFlags(config.raw_flags));
+        }
+        if config.country > MAX_COUNTRY {
+            return Err(UhidError::InvalidCountry(config.country));
         }
-        self.created = true;
 
-        let name_bytes = name.as_bytes();
+        let rdesc = &config.rdesc[..];
 
-        if name_bytes.len() > 128 {
-            return Err(format!("invalid name length: {} (max: 128)", name_bytes.len()));
+        /* the kernel copies all three with strncpy() and keeps the last byte of each field for the NUL */
+        let name = str_to_fixed("name", &config.name)
+            .map_err(|_| UhidError::InvalidNameLength(config.name.len()))?;
+        let phys = str_to_fixed("phys", &config.phys)?;
+        let uniq = str_to_fixed("uniq", &config.uniq)?;
+        /* the kernel refuses this too, but with the same EINVAL as an oversized one */
+        if rdesc.is_empty() {
+            return Err(UhidError::EmptyDescriptor);
         }
-        if rdesc.len() > 128 {
-            return Err(format!("invalid report descriptor length: {} (max: {})", name.len(), HID_MAX_DESCRIPTOR_SIZE));
+        if rdesc.len() > MAX_DESCRIPTOR_SIZE {
+            return Err(UhidError::InvalidDescriptorLength(rdesc.len()));
         }
 
         let mut create_req = Create2Req {
-            name: [0; 128],
-            phys: [0; 64],
-            uniq: [0; 64],
+            name,
+            phys,
+            uniq,
             rd_size: rdesc.len() as u16,
-            bus: bus.unwrap_or(Bus::USB as u16),
-            vendor: vid,
-            product: pid,
-            version: 0,
-            country: 0,
+            bus: config.bus as u16,
+            vendor: config.vendor,
+            product: config.product,
+            version: config.version,
+            country: config.country,
             rd_data: [0; HID_MAX_DESCRIPTOR_SIZE],
         };
 
-        /* populate the name and report descriptor data - this was the only way I found to do this */
-        create_req.name[..name_bytes.len()].clone_from_slice(name_bytes);
         create_req.rd_data[..rdesc.len()].clone_from_slice(rdesc);
 
-        let req_vec: Vec<u8> = Self::event(
-            EventType::Create2,
-            Some(bincode::serialize(&create_req).unwrap()),
+        let req_vec: Vec<u8> = Self::event(EventType::Create2, &create_req, &[])?;
+
+        let (result, legacy) = match config.creation_mode {
+            CreationMode::Create2 => (self.write_event(&req_vec), false),
+            CreationMode::Legacy => (self.write_event(&legacy_create_event(&create_req, rdesc)), true),
+            CreationMode::Auto => match self.write_event(&req_vec) {
+                /* kernels before Create2 refuse unknown event types with EOPNOTSUPP */
+                Err(UhidError::Write(e)) if matches!(e.raw_os_error(), Some(libc::EOPNOTSUPP) | Some(libc::EINVAL)) => {
+                    match self.write_event(&legacy_create_event(&create_req, rdesc)) {
+                        Ok(()) => (Ok(()), true),
+                        /* report why Create2 failed, the fallback was only a guess */
+                        Err(_) => (Err(UhidError::Write(e)), false),
+                    }
+                }
+                result => (result, false),
+            },
+        };
+        match result {
+            /* the kernel only rejects a create with EINVAL when rd_size is out of range */
+            Err(UhidError::Write(e)) if e.raw_os_error() == Some(libc::EINVAL) => {
+                return Err(UhidError::InvalidDescriptorLength(rdesc.len()));
+            }
+            Err(UhidError::Write(e)) => return Err(UhidError::Create(e)),
+            result => result?,
+        }
+        self.legacy = legacy;
+        self.state = DeviceState::Created;
+        self.config = config.clone();
+        self.open_count = 0;
+        self.start_flags = None;
+        /* the kernel has the final say on the descriptor, so a parse failure only leaves this empty */
+        self.report_lengths = match descriptor::parse_descriptor(rdesc) {
+            Ok(info) => info
+                .reports
+                .iter()
+                .filter(|report| report.kind == ReportType::Input)
+                .map(|report| (report.report_id, report.byte_length()))
+                .collect(),
+            Err(_) => HashMap::new(),
+        };
+        Ok(())
+    }
+
+    pub fn state(&self) -> DeviceState {
+        self.state
+    }
+
+    pub fn is_created(&self) -> bool {
+        self.state == DeviceState::Created
+    }
+
+    /// Name the device was created with.
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn vendor_id(&self) -> u32 {
+        self.config.vendor
+    }
+
+    pub fn product_id(&self) -> u32 {
+        self.config.product
+    }
+
+    pub fn bus(&self) -> Bus {
+        self.config.bus
+    }
+
+    /// Version the device was created with, the `bcdDevice` of USB devices.
+    ///
+    /// Like the other identifiers, it is sent to the kernel in the Create2 event and can't change
+    /// while the device exists. Use [`reset`](Self::reset) to recreate the device with another one.
+    pub fn version(&self) -> u32 {
+        self.config.version
+    }
+
+    /// Path of the device under `/sys/bus/hid/devices`, once the kernel has registered it.
+    pub fn sysfs_path(&self) -> Result<PathBuf, UhidError> {
+        let prefix = format!(
+            "{:04X}:{:04X}:{:04X}.",
+            self.config.bus as u16, self.config.vendor, self.config.product
         );
+        let name = format!("HID_NAME={}", self.config.name);
+
+        let entries = fs::read_dir("/sys/bus/hid/devices").map_err(|_| UhidError::SysfsNotFound)?;
+        let mut candidates: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+            .map(|entry| entry.path())
+            .filter(|path| match fs::canonicalize(path) {
+                /* only consider devices created through uhid */
+                Ok(real) => real.components().any(|c| c.as_os_str() == "uhid"),
+                Err(_) => false,
+            })
+            .filter(|path| match fs::read_to_string(path.join("uevent")) {
+                Ok(uevent) => uevent.lines().any(|line| line == name),
+                Err(_) => false,
+            })
+            .collect();
+
+        /* the newest device has the highest sequence number */
+        candidates.sort();
+        candidates.pop().ok_or(UhidError::SysfsNotFound)
+    }
+
+    /// Name of the HID driver bound to the device, such as `hid-generic`, or `None` if unbound.
+    ///
+    /// Fails with [`UhidError::SysfsNotFound`] until the kernel has registered the device.
+    pub fn bound_driver(&self) -> Result<Option<String>, UhidError> {
+        Ok(driver_name(&self.sysfs_path()?))
+    }
+
+    /// Report descriptor as stored by the kernel, read from `report_descriptor` in sysfs.
+    ///
+    /// This should be the descriptor the device was created with, byte for byte. Fails with
+    /// [`UhidError::SysfsNotFound`] until the kernel has registered the device.
+    pub fn kernel_descriptor(&self) -> Result<Vec<u8>, UhidError> {
+        fs::read(self.sysfs_path()?.join("report_descriptor")).map_err(UhidError::Sysfs)
+    }
+
+    /// Modalias of the device as set by the kernel, the string drivers and udev rules are matched
+    /// against, such as `hid:b0003g0001v0000046Dp0000C077`.
+    ///
+    /// Fails with [`UhidError::SysfsNotFound`] until the kernel has registered the device. See
+    /// [`DeviceConfig::modalias_pattern`] for the same without a device.
+    pub fn modalias(&self) -> Result<String, UhidError> {
+        let modalias = fs::read_to_string(self.sysfs_path()?.join("modalias")).map_err(UhidError::Sysfs)?;
+        Ok(modalias.trim_end().to_string())
+    }
+
+    pub fn destroy(&mut self) -> Result<(), UhidError> {
+        self.state = DeviceState::Destroyed;
+        self.open_count = 0;
+        self.start_flags = None;
+
+        self.write_event(&Self::event(EventType::Destroy, &(), &[])?)
+    }
 
-        match self.uhid_fd.write(&req_vec) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(format!("failed to send event ({})", e)),
+    /// Length of every input report of the device, by report ID, as parsed from the descriptor at
+    /// creation. Lengths include the report ID byte of numbered reports.
+    ///
+    /// This is empty if the descriptor could not be parsed.
+    pub fn report_lengths(&self) -> &HashMap<Option<u8>, usize> {
+        &self.report_lengths
+    }
+
+    /// Sends an all-zero input report for every report in [`report_lengths`](Self::report_lengths),
+    /// in report ID order, which for most devices means nothing pressed and no movement.
+    pub fn send_neutral(&mut self) -> Result<(), UhidError> {
+        let mut reports: Vec<_> = self.report_lengths.iter().map(|(&id, &len)| (id, len)).collect();
+        reports.sort();
+
+        for (id, len) in reports {
+            let mut report = vec![0; len];
+            if let Some(id) = id {
+                report[0] = id;
+            }
+            self.input(&report)?;
         }
+        Ok(())
     }
 
-    pub fn destroy(&mut self) -> Result<(), String> {
-        self.created = false;
+    /// Destroys the device if it exists and creates it again with `config`.
+    ///
+    /// This is the only way to change the identifiers, descriptor or strings of a device, as the
+    /// kernel fixes them at creation. Host software sees the old device go away and a new one
+    /// appear.
+    pub fn reset(&mut self, config: &DeviceConfig) -> Result<(), UhidError> {
+        if self.is_created() {
+            self.destroy()?;
+        }
+        self.create_with(config)
+    }
 
-        match self.uhid_fd.write(&Self::event(EventType::Destroy, None)) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(format!("failed to send event ({})", e)),
+    /// Simulates unplugging and replugging the device.
+    ///
+    /// Destroys the device, waits for `pause`, then creates it again with the same configuration.
+    /// This does not wait for the kernel to start the new device, read events until
+    /// [`Event::Start`] for that.
+    pub fn reconnect(&mut self, pause: Duration) -> Result<(), UhidError> {
+        if !self.is_created() {
+            return Err(UhidError::NotCreated);
         }
+        let config = self.config.clone();
+
+        self.destroy()?;
+        thread::sleep(pause);
+        self.create_with(&config)
     }
-}
 
-pub struct EpollDevice {
-    uhid_dev: Device,
-    epoll_fd: RawFd,
-}
+    /// Like [`reconnect`](Self::reconnect), but pauses for a random duration within `jitter` of
+    /// `base`, to exercise host reconnection logic with uneven timing. Returns the pause used.
+    ///
+    /// The same `seed` always gives the same pause, for reproducible tests; without one, the clock
+    /// seeds it. The generator is a plain splitmix64, good enough for timing but nothing else.
+    pub fn reconnect_jittered(
+        &mut self,
+        base: Duration,
+        jitter: Duration,
+        seed: Option<u64>,
+    ) -> Result<Duration, UhidError> {
+        let seed = seed.unwrap_or_else(|| match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(now) => now.as_nanos() as u64,
+            Err(_) => 0,
+        });
+        let pause = jittered(base, jitter, seed);
+        self.reconnect(pause)?;
+        Ok(pause)
+    }
 
-impl EpollDevice {
-    pub fn new() -> Result<Self, String> {
-        Ok(EpollDevice {
-            uhid_dev: Device::new()?,
-            epoll_fd: match epoll::create(false) {
-                Ok(fd) => fd,
-                Err(e) => return Err(format!("failed to open the epoll file descriptor ({})", e)),
-            },
-        })
+    /// Reconnects the device `times` times, leaving it connected for `pause` after each cycle.
+    pub fn reconnect_loop(&mut self, times: usize, pause: Duration) -> Result<(), UhidError> {
+        for _ in 0..times {
+            self.reconnect(pause)?;
+            thread::sleep(pause);
+        }
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Sets how [`input`](Self::input) handles a full kernel queue.
+    pub fn set_backpressure(&mut self, backpressure: Backpressure) {
+        self.backpressure = backpressure;
+    }
 
-    const MOUSE_RDEC: [u8; 55] = [
-        0x05, 0x01,  // Usage Page (Generic Desktop)        0
-        0x09, 0x02,  // Usage (Mouse)                       2
-        0xa1, 0x01,  // Collection (Application)            4
-        0x09, 0x02,  // .Usage (Mouse)                      6
-        0xa1, 0x02,  // .Collection (Logical)               8
-        0x09, 0x01,  // ..Usage (Pointer)                   10
-        0xa1, 0x00,  // ..Collection (Physical)             12
-        0x05, 0x09,  // ...Usage Page (Button)              14
-        0x19, 0x01,  // ...Usage Minimum (1)                16
-        0x29, 0x03,  // ...Usage Maximum (3)                18
-        0x15, 0x00,  // ...Logical Minimum (0)              20
-        0x25, 0x01,  // ...Logical Maximum (1)              22
-        0x75, 0x01,  // ...Report Size (1)                  24
-        0x95, 0x03,  // ...Report Count (3)                 26
-        0x81, 0x02,  // ...Input (Data,Var,Abs)             28
-        0x75, 0x05,  // ...Report Size (5)                  30
-        0x95, 0x01,  // ...Report Count (1)                 32
-        0x81, 0x03,  // ...Input (Cnst,Var,Abs)             34
-        0x05, 0x01,  // ...Usage Page (Generic Desktop)     36
-        0x09, 0x30,  // ...Usage (X)                        38
-        0x09, 0x31,  // ...Usage (Y)                        40
-        0x15, 0x81,  // ...Logical Minimum (-127)           42
-        0x25, 0x7f,  // ...Logical Maximum (127)            44
-        0x75, 0x08,  // ...Report Size (8)                  46
-        0x95, 0x02,  // ...Report Count (2)                 48
-        0x81, 0x06,  // ...Input (Data,Var,Rel)             50
-        0xc0,        // ..End Collection                    52
-        0xc0,        // .End Collection                     53
-        0xc0,        // End Collection                      54
-    ];
+    /// Sends an input report (`UHID_INPUT2`).
+    ///
+    /// The event is written as the 4-byte type, the 2-byte little-endian `size` and then exactly
+    /// `data.len()` bytes. `uhid_input2_req` has a fixed 4096-byte data field, but the kernel
+    /// zero-fills its event buffer before copying a write into it and only uses the first `size`
+    /// bytes, so the trailing padding does not need to be sent.
+    ///
+    /// Reports longer than that data field fail with [`UhidError::ReportTooLong`], which also
+    /// keeps the length within the 16-bit `size`.
+    pub fn input(&mut self, data: &[u8]) -> Result<(), UhidError> {
+        if !self.is_created() {
+            return Err(UhidError::NotCreated);
+        }
+        if data.len() > HID_MAX_DESCRIPTOR_SIZE {
+            return Err(UhidError::ReportTooLong(data.len()));
+        }
 
-    #[test]
-    fn create() {
-        assert_eq!(2 + 2, 4);
+        let event = if self.legacy {
+            /* struct uhid_input_req: data[UHID_DATA_MAX], size */
+            let mut event = Self::event(EventType::__LegacyInput, &(), data)?;
+            event.resize(4 + HID_MAX_DESCRIPTOR_SIZE, 0);
+            event.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            event
+        } else {
+            Self::event(EventType::Input2, &(data.len() as u16), data)?
+        };
 
-        let mut dev = Device::new().unwrap();
-        dev.create(
-            0x1234,
-            0x4321,
-            "my rust UHID device!",
-            &MOUSE_RDEC,
-            None,
-        ).unwrap();
+        loop {
+            match self.write_event(&event) {
+                Err(UhidError::Write(e)) if e.kind() == io::ErrorKind::WouldBlock => match self.backpressure {
+                    Backpressure::Fail => return Err(UhidError::QueueFull),
+                    Backpressure::Block => self.uhid_fd.wait_writable().map_err(UhidError::Input)?,
+                },
+                Err(UhidError::Write(e)) => return Err(UhidError::Input(e)),
+                Err(e) => return Err(e),
+                Ok(()) => {
+                    self.stats.inputs_sent += 1;
+                    self.stats.bytes_sent += data.len() as u64;
+                    #[cfg(feature = "latency-stats")]
+                    if let Some(read_at) = self.output_read_at.take() {
+                        self.latency.turnaround.record(read_at.elapsed());
+                    }
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Like [`input`](Self::input), but first checks `data` against the descriptor.
+    ///
+    /// The kernel accepts input reports of any length and the HID core then drops the ones that
+    /// don't match the descriptor without telling anyone. This instead fails with
+    /// [`UhidError::ReportLengthMismatch`], or [`UhidError::UnknownReport`] for a report ID the
+    /// descriptor doesn't declare, using the [`report_lengths`](Self::report_lengths) parsed at
+    /// creation. If the descriptor could not be parsed, the report is sent unchecked.
+    pub fn input_checked(&mut self, data: &[u8]) -> Result<(), UhidError> {
+        if !self.is_created() {
+            return Err(UhidError::NotCreated);
+        }
+        if !self.report_lengths.is_empty() {
+            let numbered = self.report_lengths.keys().any(Option::is_some);
+            let id = if numbered { data.first().copied() } else { None };
+            match self.report_lengths.get(&id) {
+                Some(&expected) if expected != data.len() => {
+                    return Err(UhidError::ReportLengthMismatch { expected, got: data.len() })
+                }
+                Some(_) => (),
+                None => return Err(UhidError::UnknownReport(id)),
+            }
+        }
+        self.input(data)
+    }
+
+    /// Flushes the transport.
+    ///
+    /// Every event is written to the kernel as soon as it is sent, so with `/dev/uhid` this has
+    /// nothing to do. There is no buffered mode to trade latency for throughput: uhid takes exactly
+    /// one event per `write()` and silently drops whatever follows it in the same call, so several
+    /// events can't be batched into one write. This is only useful with custom transports that
+    /// buffer.
+    pub fn flush(&mut self) -> Result<(), UhidError> {
+        self.uhid_fd.flush().map_err(UhidError::Write)
+    }
+
+    /// Reads the next event sent by the kernel.
+    ///
+    /// Events are read into a buffer owned by the device, without allocating one per call. The
+    /// returned event owns its data, so it stays valid across later reads.
+    ///
+    /// GetReport requests for a report registered with
+    /// [`set_feature_report`](Self::set_feature_report) are answered here and not returned.
+    pub fn read_event(&mut self) -> Result<Event, UhidError> {
+        #[cfg(feature = "latency-stats")]
+        let called_at = Instant::now();
+        loop {
+            if let Some(event) = self.read_one_event()? {
+                #[cfg(feature = "latency-stats")]
+                {
+                    let now = Instant::now();
+                    self.latency.read.record(now - called_at);
+                    if let Event::Output { .. } = event {
+                        self.output_read_at = Some(now);
+                    }
+                }
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Largest size of the event being read, from its type at the start of the read buffer.
+    fn event_size(&self) -> usize {
+        event_buffer_size(u32::from_le_bytes([self.read_buf[0], self.read_buf[1], self.read_buf[2], self.read_buf[3]]))
+    }
+
+    /// Reads a single event, returns `None` if it was answered automatically.
+    fn read_one_event(&mut self) -> Result<Option<Event>, UhidError> {
+        let mut filled = 0;
+        /* the kernel hands out a whole event per read, other transports may split it. Kernels
+           before 3.18, which only take the legacy events, have a smaller struct uhid_event: there
+           stop as soon as the event is complete rather than waiting for the next one */
+        while filled < self.read_buf.len() && !(self.legacy && filled >= 4 && filled >= self.event_size()) {
+            match self.uhid_fd.read(&mut self.read_buf[filled..]) {
+                Ok(0) => return Err(UhidError::UnexpectedEof),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(UhidError::Read(e)),
+            }
+        }
+        let event = Event::parse(&self.read_buf[..filled])?;
+
+        match event {
+            Event::Output { .. } => self.stats.outputs_received += 1,
+            Event::GetReport { .. } => self.stats.get_reports += 1,
+            Event::SetReport { .. } => self.stats.set_reports += 1,
+            Event::Start { dev_flags } => self.start_flags = Some(dev_flags),
+            Event::Open => self.open_count += 1,
+            Event::Close => self.open_count = self.open_count.saturating_sub(1),
+            _ => (),
+        }
+
+        if let Event::GetReport { id, rnum, rtype: ReportType::Feature } = event {
+            if let Some(data) = self.feature_reports.get(&rnum).cloned() {
+                self.get_report_reply(id, 0, &data)?;
+                return Ok(None);
+            }
+        }
+        Ok(Some(event))
+    }
+
+    /// Answers GetReport requests for feature report `rnum` with `data` from now on.
+    ///
+    /// `data` is the report as sent, starting with the report ID if the report is numbered. The
+    /// report also has to be declared in the descriptor for the host to ask for it.
+    pub fn set_feature_report(&mut self, rnum: u8, data: &[u8]) {
+        self.feature_reports.insert(rnum, data.to_vec());
+    }
+
+    /// Serves `serial` as the feature report `rnum`, see [`set_feature_report`](Self::set_feature_report).
+    ///
+    /// The report holds the report ID, unless `rnum` is 0, followed by the serial as UTF-8. This
+    /// is for vendor software that reads the serial with a feature request (`HIDIOCGFEATURE` on
+    /// hidraw). Most other software uses the `uniq` field instead, see [`DeviceBuilder::serial`].
+    pub fn set_serial_feature(&mut self, rnum: u8, serial: &str) {
+        let mut data = Vec::with_capacity(serial.len() + 1);
+        if rnum != 0 {
+            data.push(rnum);
+        }
+        data.extend_from_slice(serial.as_bytes());
+        self.set_feature_report(rnum, &data);
+    }
+
+    /// Serves `percent` as the battery level of a [`presets::battery`] report with `report_id`.
+    ///
+    /// Levels above 100 are sent as 100. The kernel reads the level when it sets the device up and
+    /// whenever userspace reads the power supply's capacity, there is no way to push a change.
+    pub fn set_battery_level(&mut self, report_id: u8, percent: u8) {
+        self.set_feature_report(report_id, &[report_id, percent.min(100)]);
+    }
+
+    /// Iterates over events, blocking until each one arrives.
+    ///
+    /// The iterator only ends after yielding a Stop event, after which the device no longer
+    /// receives anything, or a read error.
+    pub fn blocking_events(&mut self) -> BlockingEvents<'_, T> {
+        BlockingEvents { device: self, done: false }
+    }
+
+    /// Iterates over the events that are pending right now, without blocking.
+    ///
+    /// The iterator ends as soon as no event is pending, or after yielding a read error. It polls
+    /// before each read, so it works the same whether or not the fd is nonblocking.
+    pub fn nonblocking_events(&mut self) -> NonblockingEvents<'_, T> {
+        NonblockingEvents { device: self, done: false }
+    }
+
+    /// Reads and discards every event that is immediately available, returns how many there were.
+    ///
+    /// This polls before each read, so it never blocks, whether or not the fd is nonblocking.
+    pub fn drain(&mut self) -> Result<usize, UhidError> {
+        let mut count = 0;
+        while self.uhid_fd.wait_readable(Some(Duration::ZERO)).map_err(UhidError::Read)? {
+            self.read_one_event()?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Returns whether something on the host has the device open, as seen in the events read so far.
+    ///
+    /// The kernel only sends Open when the first consumer opens the device and Close when the last
+    /// one closes it, not once per consumer, so this tells whether anyone is listening but not how
+    /// many.
+    pub fn is_open(&self) -> bool {
+        self.open_count > 0
+    }
+
+    /// Which report types the driver expects to be numbered, from the Start event.
+    ///
+    /// Returns `None` until the Start event of the current device has been read. When a flag is
+    /// set, reports of that type carry their report ID as the first byte.
+    pub fn numbered_reports(&self) -> Option<StartFlags> {
+        self.start_flags
+    }
+
+    /// Snapshot of the traffic counters.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Snapshot of the latencies measured so far, see [`latency::LatencyStats`] for what is
+    /// measured.
+    ///
+    /// Only available with the `latency-stats` feature. The timestamps are taken with
+    /// [`Instant`] in the calling thread, so they include the time the thread waited to be
+    /// scheduled but not the time the event spent in the kernel before `read_event()` was called.
+    #[cfg(feature = "latency-stats")]
+    pub fn latency_stats(&self) -> latency::LatencyStats {
+        self.latency.clone()
+    }
+
+    /// Reads the next event if one arrives before `deadline`.
+    fn read_event_until(&mut self, deadline: Instant) -> Result<Option<Event>, UhidError> {
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.uhid_fd.wait_readable(Some(timeout)) {
+                Ok(true) => {
+                    if let Some(event) = self.read_one_event()? {
+                        return Ok(Some(event));
+                    }
+                }
+                Ok(false) => return Ok(None),
+                Err(e) => return Err(UhidError::Read(e)),
+            }
+        }
+    }
+
+    /// Creates the device and waits for the kernel to bring it up.
+    ///
+    /// The kernel sends Start once it registered the device and a driver started using it, and Open
+    /// once a consumer (hidraw, an input handler, ...) opens it. This returns at whichever of the two
+    /// is read first, which is normally Start, and fails with [`UhidError::Timeout`] if neither
+    /// arrives within `timeout`. Any other event read while waiting is discarded.
+    pub fn create_and_wait(&mut self, config: &DeviceConfig, timeout: Duration) -> Result<(), UhidError> {
+        self.create_with(config)?;
+        self.wait_for(|event| matches!(event, Event::Start { .. } | Event::Open), timeout)?;
+        Ok(())
+    }
+
+    /// Creates the device and waits for the kernel to confirm it came up.
+    ///
+    /// The kernel sends Start when a driver starts setting the device up, and Stop right after if
+    /// the driver then fails to, from the same call. This waits for Start, then reads the events
+    /// already queued behind it: if one of them is Stop, the device is destroyed again and left
+    /// uninitialized, so the call can be retried, and this fails with [`UhidError::CreateRejected`].
+    /// Any other event read meanwhile is discarded.
+    ///
+    /// If no driver binds within `timeout`, this fails with [`UhidError::Timeout`] and the device
+    /// is left created, so the caller can keep waiting with [`wait_for`](Self::wait_for) or
+    /// destroy it.
+    pub fn create_confirmed(&mut self, config: &DeviceConfig, timeout: Duration) -> Result<(), UhidError> {
+        self.create_with(config)?;
+        let event = self.wait_for(|event| matches!(event, Event::Start { .. } | Event::Stop), timeout)?;
+        let mut stopped = event == Event::Stop;
+        while !stopped && self.uhid_fd.wait_readable(Some(Duration::ZERO)).map_err(UhidError::Read)? {
+            stopped = matches!(self.read_one_event()?, Some(Event::Stop));
+        }
+        if stopped {
+            /* best effort, the device may already be gone */
+            let _ = self.destroy();
+            self.state = DeviceState::Uninitialized;
+            self.config = DeviceConfig::default();
+            return Err(UhidError::CreateRejected);
+        }
+        Ok(())
+    }
+
+    /// Reads events until one matches `predicate` and returns it, discarding the others.
+    ///
+    /// Fails with [`UhidError::Timeout`] if no matching event arrives within `timeout`.
+    pub fn wait_for(&mut self, predicate: impl Fn(&Event) -> bool, timeout: Duration) -> Result<Event, UhidError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.read_event_until(deadline)? {
+                Some(event) if predicate(&event) => return Ok(event),
+                Some(_) => (),
+                None => return Err(UhidError::Timeout),
+            }
+        }
+    }
+
+    /// Creates the device and sends `initial` as its first input report.
+    ///
+    /// If the report can't be sent, the device is destroyed again and left uninitialized, so the
+    /// call can simply be retried. The error of the input report is returned.
+    pub fn create_with_initial_report(&mut self, config: &DeviceConfig, initial: &[u8]) -> Result<(), UhidError> {
+        self.create_with(config)?;
+
+        if let Err(e) = self.input(initial) {
+            /* best effort, the device may already be gone */
+            let _ = self.destroy();
+            self.state = DeviceState::Uninitialized;
+            self.config = DeviceConfig::default();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Answers a [`Event::GetReport`], `err` is an errno value or 0 on success.
+    pub fn get_report_reply(&mut self, id: u32, err: u16, data: &[u8]) -> Result<(), UhidError> {
+        if data.len() > HID_MAX_DESCRIPTOR_SIZE {
+            return Err(UhidError::ReportTooLong(data.len()));
+        }
+        self.write_event(&Self::event(EventType::GetReportReply, &(id, err, data.len() as u16), data)?)?;
+        self.answered(id);
+        Ok(())
+    }
+
+    /// Answers a [`Event::SetReport`], `err` is an errno value or 0 on success.
+    pub fn set_report_reply(&mut self, id: u32, err: u16) -> Result<(), UhidError> {
+        self.write_event(&Self::event(EventType::SetReportReply, &(id, err), &[])?)?;
+        self.answered(id);
+        Ok(())
+    }
+
+    fn answered(&mut self, id: u32) {
+        if self.unanswered == Some(id) {
+            self.unanswered = None;
+        }
+    }
+
+    /// Reads events and passes them to `handler` until the device is stopped.
+    ///
+    /// Returns after the handler has seen a Stop event, or with the first error of a read or of
+    /// the handler. If the handler returns without answering a GetReport or SetReport request, the
+    /// request is answered with `EIO`, as the host would otherwise block until it times out. Turn
+    /// this off with [`set_auto_reply`](Self::set_auto_reply) to answer requests later.
+    pub fn run(&mut self, mut handler: impl FnMut(&mut Self, Event) -> Result<(), UhidError>) -> Result<(), UhidError> {
+        loop {
+            let event = self.read_event()?;
+            if self.dispatch(event, &mut handler)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Like [`run`](Self::run), but also stops once `running` is cleared, from another thread for
+    /// example, destroying the device before returning.
+    ///
+    /// The flag is checked before every event and at least every [`SHUTDOWN_POLL_INTERVAL`] while
+    /// no event arrives. An event being handled when the flag is cleared is handled to the end.
+    pub fn run_while(
+        &mut self,
+        running: &AtomicBool,
+        mut handler: impl FnMut(&mut Self, Event) -> Result<(), UhidError>,
+    ) -> Result<(), UhidError> {
+        while running.load(Ordering::Acquire) {
+            if let Some(event) = self.read_event_until(Instant::now() + SHUTDOWN_POLL_INTERVAL)? {
+                if self.dispatch(event, &mut handler)? {
+                    return Ok(());
+                }
+            }
+        }
+        self.destroy()
+    }
+
+    /// Passes `event` to `handler` for [`run`](Self::run), returns whether it was a Stop event.
+    fn dispatch(
+        &mut self,
+        event: Event,
+        handler: &mut impl FnMut(&mut Self, Event) -> Result<(), UhidError>,
+    ) -> Result<bool, UhidError> {
+        let stop = event == Event::Stop;
+        let request = match event {
+            Event::GetReport { id, .. } => Some((id, true)),
+            Event::SetReport { id, .. } => Some((id, false)),
+            _ => None,
+        };
+        self.unanswered = request.map(|(id, _)| id);
+
+        if let Event::Output { data, rtype: ReportType::Output } = &event {
+            let report_id = match self.start_flags {
+                Some(flags) if flags.contains(StartFlags::NUMBERED_OUTPUT_REPORTS) => data.first().copied(),
+                _ => Some(0),
+            };
+            if let Some(output_handler) = report_id.and_then(|id| self.output_handlers.get_mut(&id)) {
+                output_handler(data);
+                return Ok(false);
+            }
+        }
+
+        let result = handler(self, event);
+        if let (Some(id), true) = (self.unanswered.take(), self.auto_reply) {
+            match request {
+                Some((_, true)) => self.get_report_reply(id, libc::EIO as u16, &[])?,
+                _ => self.set_report_reply(id, libc::EIO as u16)?,
+            }
+        }
+        result?;
+        Ok(stop)
+    }
+
+    /// Has [`run`](Self::run) pass the output reports with `report_id` to `handler` instead of
+    /// its own handler, replacing the one registered for it before.
+    ///
+    /// The report ID is the first byte of the data once the Start event said output reports are
+    /// numbered, otherwise every output report has ID 0. `handler` gets the data as sent, report
+    /// ID included. Handles made with [`try_clone_handle`](Self::try_clone_handle) don't share the
+    /// handlers.
+    pub fn on_output(&mut self, report_id: u8, handler: OutputHandler) {
+        self.output_handlers.insert(report_id, handler);
+    }
+
+    /// Has [`run`](Self::run) decode the output reports of a [`presets::gamepad_rumble`] device
+    /// and pass them to `handler`, see [`on_output`](Self::on_output). Reports of the wrong length
+    /// are dropped.
+    pub fn on_rumble(&mut self, mut handler: impl FnMut(presets::RumbleCommand) + Send + 'static) {
+        self.on_output(
+            0,
+            Box::new(move |data| {
+                if let Some(command) = presets::RumbleCommand::from_bytes(data) {
+                    handler(command);
+                }
+            }),
+        );
+    }
+
+    /// Sets whether [`run`](Self::run) answers requests the handler left unanswered, on by default.
+    pub fn set_auto_reply(&mut self, auto_reply: bool) {
+        self.auto_reply = auto_reply;
+    }
+
+    /// Replays a recorded trace of input reports.
+    ///
+    /// Each entry holds the delay since the previous report (for the first entry, since this call
+    /// started) and the report to send. Delays are accumulated from the start of the call, so time
+    /// spent writing does not push the later reports back.
+    pub fn replay(&mut self, trace: &[(Duration, Vec<u8>)]) -> Result<(), UhidError> {
+        let mut deadline = Instant::now();
+
+        for (delay, report) in trace {
+            deadline += *delay;
+            let now = Instant::now();
+            if deadline > now {
+                thread::sleep(deadline - now);
+            }
+            self.input(report)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replays a trace of input reports recorded with wall clock timestamps.
+    ///
+    /// The first report is sent right away and every other one at the same offset from it as in
+    /// the recording, including bursts of reports with the same timestamp. The schedule runs on the
+    /// monotonic clock from the start of the call, so oversleeping on one report is caught up on the
+    /// next. It never goes back in time: if the system falls behind, late reports are sent
+    /// immediately, as are reports whose timestamp is earlier than the first one. Returns the
+    /// largest lag behind the schedule.
+    pub fn replay_timestamped(&mut self, trace: &[(SystemTime, Vec<u8>)]) -> Result<Duration, UhidError> {
+        let first = match trace.first() {
+            Some((time, _)) => *time,
+            None => return Ok(Duration::ZERO),
+        };
+        let start = Instant::now();
+        let mut max_lag = Duration::ZERO;
+
+        for (time, report) in trace {
+            let deadline = start + time.duration_since(first).unwrap_or(Duration::ZERO);
+            let now = Instant::now();
+            if deadline > now {
+                thread::sleep(deadline - now);
+            } else {
+                max_lag = max_lag.max(now - deadline);
+            }
+            self.input(report)?;
+        }
+
+        Ok(max_lag)
+    }
+
+    /// Creates the device called `name` in [`known_devices`].
+    pub fn emulate_known(&mut self, name: &str) -> Result<(), UhidError> {
+        self.create_with(&known_devices::config(name)?)
+    }
+
+    /// Creates a [`presets::boot_keyboard`] device.
+    pub fn create_boot_keyboard(&mut self, vid: u32, pid: u32, name: &str) -> Result<(), UhidError> {
+        self.create(vid, pid, name, &presets::boot_keyboard(), None)
+    }
+
+    /// Creates a [`presets::boot_mouse`] device.
+    pub fn create_boot_mouse(&mut self, vid: u32, pid: u32, name: &str) -> Result<(), UhidError> {
+        self.create(vid, pid, name, &presets::boot_mouse(), None)
+    }
+
+    /// Sends a [`presets::boot_keyboard`] report, `keys` holds the pressed key codes or 0.
+    pub fn send_boot_keyboard(&mut self, modifiers: u8, keys: &[u8; 6]) -> Result<(), UhidError> {
+        let report = presets::KeyboardReport { modifiers, keys: *keys };
+        self.input(&report.to_bytes(presets::Protocol::Boot, None))
+    }
+
+    /// Types `keys` on a [`presets::boot_keyboard`] device, pressing and releasing each in turn.
+    ///
+    /// Every key is released with an all-keys-up report before the next one is pressed, so
+    /// repeated keys register as separate presses. `inter_key` is waited after every report
+    /// except the last one.
+    pub fn type_keys(&mut self, keys: &[presets::KeyEvent], inter_key: Duration) -> Result<(), UhidError> {
+        for (i, key) in keys.iter().enumerate() {
+            self.send_boot_keyboard(key.modifiers, &[key.keycode, 0, 0, 0, 0, 0])?;
+            thread::sleep(inter_key);
+            self.send_boot_keyboard(0, &[0; 6])?;
+            if i + 1 < keys.len() {
+                thread::sleep(inter_key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Types `text` on a [`presets::boot_keyboard`] device, like [`type_keys`](Self::type_keys).
+    ///
+    /// Characters are mapped with [`keymap::char_to_keycode`], so this only covers US ASCII. If
+    /// any character can't be typed, this fails with [`UhidError::UnmappableChar`] before
+    /// sending anything.
+    pub fn type_string(&mut self, text: &str, inter_key: Duration) -> Result<(), UhidError> {
+        self.type_keys(&keymap::key_events(text)?, inter_key)
+    }
+
+    /// Types `text` in a burst, the way barcode scanners do, starting a report at most every
+    /// `min_delay`.
+    ///
+    /// Unlike [`type_string`](Self::type_string), the time spent writing counts towards the delay,
+    /// so with a zero `min_delay` reports go out as fast as the kernel takes them; when its queue
+    /// is full, this waits for room instead of failing. Some hosts drop reports that come too
+    /// close together, a millisecond or two is usually enough for them. Every key is still
+    /// released before the next press, so repeated characters register.
+    pub fn type_string_fast(&mut self, text: &str, min_delay: Duration) -> Result<(), UhidError> {
+        let keys = keymap::key_events(text)?;
+        let release = presets::KeyboardReport::default().to_bytes(presets::Protocol::Boot, None);
+        let mut next = Instant::now();
+        for key in &keys {
+            let press = presets::KeyboardReport { modifiers: key.modifiers, keys: [key.keycode, 0, 0, 0, 0, 0] };
+            for report in [press.to_bytes(presets::Protocol::Boot, None), release.clone()] {
+                thread::sleep(next.saturating_duration_since(Instant::now()));
+                next = Instant::now() + min_delay;
+                loop {
+                    match self.input(&report) {
+                        Err(UhidError::QueueFull) => self.uhid_fd.wait_writable().map_err(UhidError::Input)?,
+                        result => break result?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a [`presets::boot_mouse`] report, -128 is sent as -127 to stay in the logical range.
+    pub fn send_boot_mouse(&mut self, buttons: u8, dx: i8, dy: i8) -> Result<(), UhidError> {
+        self.send_mouse(&presets::MouseReport { buttons, dx: dx as i32, dy: dy as i32 })
+    }
+
+    /// Sends a [`presets::boot_mouse`] report, clamping the movement to the logical range.
+    pub fn send_mouse(&mut self, report: &presets::MouseReport) -> Result<(), UhidError> {
+        self.input(&report.to_bytes())
+    }
+
+    /// Sends a [`presets::dial`] report.
+    pub fn send_dial(&mut self, report: &presets::DialReport) -> Result<(), UhidError> {
+        self.input(&report.to_bytes())
+    }
+
+    /// Sends a [`presets::consumer_control`] report with `usage` pressed.
+    pub fn send_consumer(&mut self, usage: u16) -> Result<(), UhidError> {
+        self.input(&presets::ConsumerReport { usage }.to_bytes())
+    }
+
+    /// Sends a [`presets::consumer_control`] report with nothing pressed.
+    pub fn release_consumer(&mut self) -> Result<(), UhidError> {
+        self.send_consumer(0)
+    }
+}
+
+impl<T: Transport + Send + 'static> Device<T> {
+    /// Moves the device into a thread that sends the reports it receives as input reports.
+    ///
+    /// The thread destroys the device and exits once every [`InputSender`] is dropped, or after the
+    /// first failed write.
+    pub fn spawn_input_channel(mut self) -> (InputSender, JoinHandle<Result<(), UhidError>>) {
+        let (sender, receiver) = mpsc::channel::<Vec<u8>>();
+        let handle = thread::spawn(move || {
+            let result = receiver.iter().try_for_each(|report| self.input(&report));
+            let destroyed = self.destroy();
+            result.and(destroyed)
+        });
+        (InputSender(sender), handle)
+    }
+
+    /// Moves the device into a thread that forwards every event it reads to `sender`.
+    ///
+    /// The thread exits after forwarding a Stop event, on the first read error, or once the
+    /// receiving end is dropped.
+    pub fn spawn_event_reader(mut self, sender: mpsc::Sender<Event>) -> JoinHandle<Result<(), UhidError>> {
+        thread::spawn(move || loop {
+            let event = self.read_event()?;
+            let stop = event == Event::Stop;
+            if sender.send(event).is_err() || stop {
+                return Ok(());
+            }
+        })
+    }
+
+    /// Like [`spawn_event_reader`](Self::spawn_event_reader), but the thread also destroys the
+    /// device and exits once `running` is cleared, noticing it within [`SHUTDOWN_POLL_INTERVAL`].
+    pub fn spawn_event_reader_while(
+        mut self,
+        sender: mpsc::Sender<Event>,
+        running: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), UhidError>> {
+        thread::spawn(move || {
+            while running.load(Ordering::Acquire) {
+                if let Some(event) = self.read_event_until(Instant::now() + SHUTDOWN_POLL_INTERVAL)? {
+                    let stop = event == Event::Stop;
+                    if sender.send(event).is_err() || stop {
+                        return Ok(());
+                    }
+                }
+            }
+            self.destroy()
+        })
+    }
+}
+
+/// Sending half of [`Device::spawn_input_channel`].
+#[derive(Clone)]
+pub struct InputSender(mpsc::Sender<Vec<u8>>);
+
+impl InputSender {
+    /// Queues `report`, failing if the device thread has exited.
+    pub fn send(&self, report: Vec<u8>) -> Result<(), mpsc::SendError<Vec<u8>>> {
+        self.0.send(report)
+    }
+}
+
+impl<T> fmt::Display for Device<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Device {{ name: {:?}, vid: {:#06x}, pid: {:#06x}, bus: {:?}, state: {:?} }}",
+            self.config.name, self.config.vendor, self.config.product, self.config.bus, self.state
+        )
+    }
+}
+
+/// Identifies a device registered with an [`EpollDevice`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Token(pub usize);
+
+/// Set of devices waited on together with a single epoll instance.
+pub struct EpollDevice {
+    devices: Vec<Device>,
+    epoll_fd: RawFd,
+}
+
+impl EpollDevice {
+    /// Opens `/dev/uhid` and registers it as the first device, with `Token(0)`.
+    pub fn new() -> Result<Self, UhidError> {
+        let mut epoll = Self::empty()?;
+        epoll.add(Device::new()?)?;
+        Ok(epoll)
+    }
+
+    /// Creates the epoll instance without any device, register them with [`add`](Self::add).
+    ///
+    /// Fails with [`UhidError::EpollUnavailable`] where epoll is not allowed, as in some
+    /// sandboxes; [`PollDevice`] works the same way without it.
+    pub fn empty() -> Result<Self, UhidError> {
+        Ok(EpollDevice {
+            devices: Vec::new(),
+            epoll_fd: match epoll::create(true) {
+                Ok(fd) => fd,
+                Err(e) => return Err(UhidError::EpollUnavailable(e)),
+            },
+        })
+    }
+
+    /// Registers `device`, tokens are handed out in order starting from 0.
+    pub fn add(&mut self, device: Device) -> Result<Token, UhidError> {
+        let token = Token(self.devices.len());
+        let event = epoll::Event::new(epoll::Events::EPOLLIN, token.0 as u64);
+        let fd = device.uhid_fd.as_raw_fd();
+        if let Err(e) = epoll::ctl(self.epoll_fd, epoll::ControlOptions::EPOLL_CTL_ADD, fd, event) {
+            return Err(UhidError::Epoll(e));
+        }
+        self.devices.push(device);
+        Ok(token)
+    }
+
+    pub fn device(&mut self, token: Token) -> Option<&mut Device> {
+        self.devices.get_mut(token.0)
+    }
+
+    /// Number of file descriptors owned: the epoll instance plus one per device, all closed when
+    /// this is dropped.
+    pub fn fd_count(&self) -> usize {
+        1 + self.devices.len()
+    }
+
+    /// Waits up to `timeout` for devices to become readable and reads one event from each.
+    ///
+    /// At most `max_events` devices are read per call, the others are reported by the next one.
+    /// Returns an empty list on timeout.
+    pub fn wait(&mut self, max_events: usize, timeout: Duration) -> Result<Vec<(Token, Event)>, UhidError> {
+        let mut ready = vec![epoll::Event::new(epoll::Events::empty(), 0); max_events.max(1)];
+        let deadline = Some(Instant::now() + timeout);
+        let count = loop {
+            match epoll::wait(self.epoll_fd, timeout_ms(deadline), &mut ready) {
+                Ok(count) => break count,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(UhidError::Epoll(e)),
+            }
+        };
+
+        let mut events = Vec::with_capacity(count);
+        for ready in &ready[..count] {
+            let token = Token(ready.data as usize);
+            if let Some(event) = self.devices[token.0].read_one_event()? {
+                events.push((token, event));
+            }
+        }
+        Ok(events)
+    }
+}
+
+impl Drop for EpollDevice {
+    fn drop(&mut self) {
+        let _ = epoll::close(self.epoll_fd);
+    }
+}
+
+/// Set of devices waited on together with `poll()`, for where epoll is unavailable.
+///
+/// Offers the same interface as [`EpollDevice`]. Every wait passes all the devices to the kernel,
+/// so it is slower with many of them.
+pub struct PollDevice {
+    devices: Vec<Device>,
+    /* first device to read on the next wait, so none is starved by max_events */
+    next: usize,
+}
+
+impl PollDevice {
+    /// Opens `/dev/uhid` and registers it as the first device, with `Token(0)`.
+    pub fn new() -> Result<Self, UhidError> {
+        let mut poll = Self::empty();
+        poll.add(Device::new()?);
+        Ok(poll)
+    }
+
+    pub fn empty() -> Self {
+        PollDevice { devices: Vec::new(), next: 0 }
+    }
+
+    /// Registers `device`, tokens are handed out in order starting from 0.
+    pub fn add(&mut self, device: Device) -> Token {
+        self.devices.push(device);
+        Token(self.devices.len() - 1)
+    }
+
+    pub fn device(&mut self, token: Token) -> Option<&mut Device> {
+        self.devices.get_mut(token.0)
+    }
+
+    /// Number of file descriptors owned, one per device, all closed when this is dropped.
+    pub fn fd_count(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Like [`EpollDevice::wait`].
+    pub fn wait(&mut self, max_events: usize, timeout: Duration) -> Result<Vec<(Token, Event)>, UhidError> {
+        let mut pollfds: Vec<libc::pollfd> = self
+            .devices
+            .iter()
+            .map(|device| libc::pollfd { fd: device.uhid_fd.as_raw_fd(), events: libc::POLLIN, revents: 0 })
+            .collect();
+        let deadline = Some(Instant::now() + timeout);
+        while unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, timeout_ms(deadline)) } == -1 {
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::Interrupted {
+                return Err(UhidError::Read(e));
+            }
+        }
+
+        let mut events = Vec::new();
+        let (start, count) = (self.next, self.devices.len());
+        /* errors and hangups are ready too, the read reports them */
+        let ready = (0..count).map(|i| (start + i) % count).filter(|&i| pollfds[i].revents != 0);
+        for index in ready.take(max_events.max(1)) {
+            self.next = (index + 1) % count;
+            if let Some(event) = self.devices[index].read_one_event()? {
+                events.push((Token(index), event));
+            }
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+    use crate::mock::MockTransport;
+
+    const MOUSE_RDEC: [u8; 55] = hid_descriptor! {
+        UsagePage(0x01),                // Generic Desktop
+        Usage(0x02),                    // Mouse
+        Collection(0x01),               // Application
+            Usage(0x02),                // Mouse
+            Collection(0x02),           // Logical
+                Usage(0x01),            // Pointer
+                Collection(0x00),       // Physical
+                    UsagePage(0x09),    // Button
+                    UsageMinimum(1),
+                    UsageMaximum(3),
+                    LogicalMinimum(0),
+                    LogicalMaximum(1),
+                    ReportSize(1),
+                    ReportCount(3),
+                    Input(0x02),        // Data,Var,Abs
+                    ReportSize(5),
+                    ReportCount(1),
+                    Input(0x03),        // Cnst,Var,Abs
+                    UsagePage(0x01),    // Generic Desktop
+                    Usage(0x30),        // X
+                    Usage(0x31),        // Y
+                    LogicalMinimum(-127),
+                    LogicalMaximum(127),
+                    ReportSize(8),
+                    ReportCount(2),
+                    Input(0x06),        // Data,Var,Rel
+                EndCollection,
+            EndCollection,
+        EndCollection,
+    };
+
+    #[test]
+    fn mouse_rdesc_bytes() {
+        assert_eq!(MOUSE_RDEC[..6], [0x05, 0x01, 0x09, 0x02, 0xa1, 0x01]);
+        assert_eq!(MOUSE_RDEC[42..46], [0x15, 0x81, 0x25, 0x7f]);
+        assert_eq!(MOUSE_RDEC[52..], [0xc0, 0xc0, 0xc0]);
+    }
+
+    #[test]
+    #[cfg(feature = "root-tests")]
+    fn create() {
+        let mut dev = Device::new().unwrap();
+        dev.create(0x1234, 0x4321, "my rust UHID device!", &MOUSE_RDEC, None).unwrap();
+        assert!(dev.is_created());
+        dev.destroy().unwrap();
+    }
+
+    #[test]
+    fn create_event() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        assert!(!dev.is_created());
+        dev.create(0x1234, 0x4321, "my rust UHID device!", &MOUSE_RDEC, None).unwrap();
+        assert!(dev.is_created());
+
+        let written = mock.written();
+        assert_eq!(written.len(), 1);
+        let event = &written[0];
+        assert_eq!(event.len(), UHID_EVENT_SIZE);
+        assert_eq!(&event[..4], &event_type::CREATE2.to_le_bytes());
+        assert_eq!(&event[4..24], b"my rust UHID device!");
+        assert!(event[24..132].iter().all(|&b| b == 0));
+        assert_eq!(&event[260..262], &(MOUSE_RDEC.len() as u16).to_le_bytes());
+        assert_eq!(&event[280..280 + MOUSE_RDEC.len()], &MOUSE_RDEC[..]);
+    }
+
+    #[test]
+    #[cfg(feature = "root-tests")]
+    fn round_trip() {
+        let mut dev = Device::new().unwrap();
+        dev.create(0x1234, 0x4321, "my rust UHID device!", &MOUSE_RDEC, None).unwrap();
+        dev.input(&[0x01, 0x05, 0xfb]).unwrap();
+        dev.input(&[0x00, 0x00, 0x00]).unwrap();
+        dev.destroy().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "root-tests")]
+    fn country() {
+        let mut dev = Device::new().unwrap();
+        let config = DeviceBuilder::new()
+            .name("country keyboard")
+            .rdesc(&MOUSE_RDEC)
+            .vendor(0x1234)
+            .product(0x4321)
+            .country(33)
+            .unwrap()
+            .config();
+        dev.create_and_wait(&config, Duration::from_secs(5)).unwrap();
+
+        let country = fs::read_to_string(dev.sysfs_path().unwrap().join("country")).unwrap();
+        assert_eq!(u32::from_str_radix(country.trim(), 16).unwrap(), 33);
+        dev.destroy().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "root-tests")]
+    fn serial() {
+        let mut dev = Device::new().unwrap();
+        let config = DeviceBuilder::new()
+            .name("serial mouse")
+            .rdesc(&MOUSE_RDEC)
+            .vendor(0x1234)
+            .product(0x4321)
+            .serial("SN-0042")
+            .config();
+        dev.create_and_wait(&config, Duration::from_secs(5)).unwrap();
+
+        let uevent = fs::read_to_string(dev.sysfs_path().unwrap().join("uevent")).unwrap();
+        assert!(uevent.lines().any(|line| line == "HID_UNIQ=SN-0042"));
+        dev.destroy().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "root-tests")]
+    fn create_n() {
+        let config = DeviceBuilder::new().name("mouse").rdesc(&MOUSE_RDEC).serial("load").config();
+        let mut devices = Device::create_n(3, &config).unwrap();
+        assert_eq!(devices.len(), 3);
+        for dev in &mut devices {
+            dev.input(&[0x00, 0x01, 0x01]).unwrap();
+        }
+    }
+
+    #[test]
+    fn indexed_config() {
+        let config = DeviceBuilder::new().name("mouse").serial("load").config();
+        assert_eq!(super::indexed_config(&config, 2), DeviceConfig { uniq: "load-2".to_string(), ..config });
+        assert_eq!(super::indexed_config(&DeviceConfig::default(), 0).uniq, "0");
+    }
+
+    #[test]
+    #[cfg(feature = "root-tests")]
+    fn bound_driver() {
+        let mut dev = Device::new().unwrap();
+        let config = DeviceBuilder::new().name("bound mouse").rdesc(&MOUSE_RDEC).config();
+        dev.create_and_wait(&config, Duration::from_secs(5)).unwrap();
+        assert!(dev.bound_driver().unwrap().is_some());
+        dev.destroy().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "root-tests")]
+    fn kernel_descriptor() {
+        let mut dev = Device::new().unwrap();
+        let config = DeviceBuilder::new().name("descriptor mouse").rdesc(&MOUSE_RDEC).config();
+        dev.create_and_wait(&config, Duration::from_secs(5)).unwrap();
+        assert_eq!(dev.kernel_descriptor().unwrap(), MOUSE_RDEC);
+        dev.destroy().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "root-tests")]
+    fn kernel_event_size() {
+        /* assumes the struct uhid_event of 3.18 and newer, older kernels send 4108-byte events */
+        let mut dev = match Device::new() {
+            Ok(dev) => dev,
+            Err(UhidError::Open(e))
+                if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied) =>
+            {
+                eprintln!("skipping kernel_event_size, /dev/uhid is not accessible: {}", e);
+                return;
+            }
+            Err(e) => panic!("unexpected error: {:?}", e),
+        };
+        let config = DeviceBuilder::new().name("framing mouse").rdesc(&MOUSE_RDEC).config();
+        dev.create_with(&config).unwrap();
+
+        /* read with room to spare, the kernel sends the whole struct uhid_event and no more */
+        let mut buf = vec![0; 2 * UHID_EVENT_SIZE];
+        assert!(dev.uhid_fd.wait_readable(Some(Duration::from_secs(5))).unwrap());
+        let len = dev.uhid_fd.read(&mut buf).unwrap();
+        assert_eq!(len, UHID_EVENT_SIZE);
+        /* no report IDs in the descriptor, so none of the numbered report flags */
+        assert_eq!(Event::parse(&buf[..len]).unwrap(), Event::Start { dev_flags: StartFlags::empty() });
+        dev.destroy().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "root-tests")]
+    fn modalias() {
+        let mut dev = Device::new().unwrap();
+        let config = DeviceBuilder::new()
+            .name("modalias mouse")
+            .rdesc(&MOUSE_RDEC)
+            .vendor(0x1234)
+            .product(0xabcd)
+            .config();
+        dev.create_and_wait(&config, Duration::from_secs(5)).unwrap();
+        assert_eq!(dev.modalias().unwrap(), "hid:b0003g0001v00001234p0000ABCD");
+        dev.destroy().unwrap();
+    }
+
+    #[test]
+    fn modalias_pattern() {
+        let config = DeviceConfig { bus: Bus::BLUETOOTH, vendor: 0x046d, product: 0xc077, ..DeviceConfig::default() };
+        assert_eq!(config.modalias_pattern(), "hid:b0005g*v0000046Dp0000C077");
+        let config = DeviceConfig { vendor: 0x1234_5678, product: 0xabcd, ..DeviceConfig::default() };
+        assert_eq!(config.modalias_pattern(), "hid:b0003g*v12345678p0000ABCD");
+    }
+
+    #[test]
+    fn driver_name() {
+        let dir = std::env::temp_dir().join(format!("uhid-rs-driver-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(super::driver_name(&dir), None);
+
+        std::os::unix::fs::symlink("../../../../bus/hid/drivers/hid-generic", dir.join("driver")).unwrap();
+        assert_eq!(super::driver_name(&dir), Some("hid-generic".to_string()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn nonblocking() {
+        let fd = OwnedFd::from(File::open("/dev/null").unwrap());
+        let mut dev = Device::from_fd(fd);
+
+        assert!(!dev.is_nonblocking().unwrap());
+        dev.set_nonblocking(true).unwrap();
+        assert!(dev.is_nonblocking().unwrap());
+        dev.set_nonblocking(false).unwrap();
+        assert!(!dev.is_nonblocking().unwrap());
+    }
+
+    #[test]
+    fn dry_run() {
+        #[derive(Clone, Default)]
+        struct Capture(Arc<std::sync::Mutex<Vec<u8>>>);
+
+        impl Write for Capture {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let capture = Capture::default();
+        let mut dev = Device::dry_run_to(capture.clone());
+        dev.create(0x1234, 0x4321, "dry run", &MOUSE_RDEC, None).unwrap();
+        dev.input(&[0x01, 0x00, 0x00]).unwrap();
+        dev.destroy().unwrap();
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("uhid dry-run: Create2 (4376 bytes): 0b 00 00 00 64 72 79 20 72 75 6e 00"));
+        /* the header and the 55 descriptor bytes, then the padding */
+        assert!(lines[0].ends_with(" c0 c0 c0 [4041 zero bytes]"));
+        assert_eq!(lines[0].len(), "uhid dry-run: Create2 (4376 bytes): ".len() + 335 * 3 + "[4041 zero bytes]".len());
+        assert_eq!(lines[1], "uhid dry-run: Input2 (9 bytes): 0c 00 00 00 03 00 01 00 00");
+        assert_eq!(lines[2], "uhid dry-run: Destroy (4 bytes): 01 00 00 00");
+
+        capture.0.lock().unwrap().clear();
+        let mut dev = Device::dry_run_to(capture.clone());
+        let config = DeviceBuilder::new().name("dry run").rdesc(&MOUSE_RDEC).creation_mode(CreationMode::Legacy);
+        dev.create_with(&config.config()).unwrap();
+        dev.input(&[0x01, 0x02, 0x03]).unwrap();
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        let create = format!("uhid dry-run: Create ({} bytes): 00 00 00 00 64 72", LEGACY_CREATE_SIZE);
+        assert!(lines[0].starts_with(&create));
+        assert_eq!(lines[1], "uhid dry-run: Input (4102 bytes): 08 00 00 00 01 02 03 [4093 zero bytes] 03 00");
+    }
+
+    #[test]
+    fn input_size() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "input size", &MOUSE_RDEC, None).unwrap();
+
+        dev.input(&[0x01, 0x02, 0x03]).unwrap();
+        dev.input(&[0xaa; 300]).unwrap();
+
+        let written = mock.written();
+        assert_eq!(written[1], [12, 0, 0, 0, 3, 0, 0x01, 0x02, 0x03]);
+        assert_eq!(written[2].len(), 4 + 2 + 300);
+        assert_eq!(&written[2][..6], &[12, 0, 0, 0, 0x2c, 0x01]);
+        assert!(written[2][6..].iter().all(|&b| b == 0xaa));
+    }
+
+    #[test]
+    fn descriptor_length() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+
+        let rdesc = vec![0; MAX_DESCRIPTOR_SIZE + 1];
+        match dev.create(0x1234, 0x4321, "too long", &rdesc, None) {
+            Err(UhidError::InvalidDescriptorLength(len)) => assert_eq!(len, MAX_DESCRIPTOR_SIZE + 1),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(mock.written().is_empty());
+
+        mock.fail_next_write(io::Error::from_raw_os_error(libc::EINVAL));
+        match dev.create(0x1234, 0x4321, "rejected", &MOUSE_RDEC, None) {
+            Err(UhidError::InvalidDescriptorLength(len)) => assert_eq!(len, MOUSE_RDEC.len()),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let rdesc = vec![0; MAX_DESCRIPTOR_SIZE];
+        dev.create(0x1234, 0x4321, "max length", &rdesc, None).unwrap();
+    }
+
+    #[test]
+    fn consumer() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "media keys", &presets::consumer_control(), None).unwrap();
+
+        dev.send_consumer(presets::ConsumerReport::PLAY_PAUSE).unwrap();
+        dev.release_consumer().unwrap();
+
+        let written = mock.written();
+        assert_eq!(&written[1][4..], &[2, 0, 0xcd, 0x00]);
+        assert_eq!(&written[2][4..], &[2, 0, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn config_accessors() {
+        let mut dev = Device::with_transport(MockTransport::new());
+        dev.create(0x1234, 0x4321, "my mouse", &MOUSE_RDEC, Some(Bus::BLUETOOTH)).unwrap();
+
+        assert_eq!(dev.name(), "my mouse");
+        assert_eq!(dev.vendor_id(), 0x1234);
+        assert_eq!(dev.product_id(), 0x4321);
+        assert_eq!(dev.bus(), Bus::BLUETOOTH);
+    }
+
+    #[test]
+    fn backpressure() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "backpressure", &MOUSE_RDEC, None).unwrap();
+
+        mock.fail_next_write(io::Error::from(io::ErrorKind::WouldBlock));
+        match dev.input(&[0x00, 0x01, 0x01]) {
+            Err(UhidError::QueueFull) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(mock.written().len(), 1);
+
+        dev.set_backpressure(Backpressure::Block);
+        mock.fail_next_write(io::Error::from(io::ErrorKind::WouldBlock));
+        mock.fail_next_write(io::Error::from(io::ErrorKind::WouldBlock));
+        dev.input(&[0x00, 0x01, 0x01]).unwrap();
+        assert_eq!(mock.written().len(), 2);
+    }
+
+    #[test]
+    fn display() {
+        let mut dev = Device::with_transport(MockTransport::new());
+        dev.create(0x1234, 0x4321, "my mouse", &MOUSE_RDEC, None).unwrap();
+        assert_eq!(
+            dev.to_string(),
+            "Device { name: \"my mouse\", vid: 0x1234, pid: 0x4321, bus: USB, state: Created }"
+        );
+
+        dev.destroy().unwrap();
+        assert!(dev.to_string().ends_with("state: Destroyed }"));
+    }
+
+    #[test]
+    fn input_channel() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "input channel", &MOUSE_RDEC, None).unwrap();
+
+        let (sender, handle) = dev.spawn_input_channel();
+        sender.send(vec![0x01, 0x00, 0x00]).unwrap();
+        sender.send(vec![0x00, 0x02, 0xfe]).unwrap();
+        drop(sender);
+        handle.join().unwrap().unwrap();
+
+        let written = mock.written();
+        assert_eq!(written.len(), 4);
+        assert_eq!(&written[1][6..], &[0x01, 0x00, 0x00]);
+        assert_eq!(&written[2][6..], &[0x00, 0x02, 0xfe]);
+        assert_eq!(written[3], [1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn create2_layout() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create_with(&DeviceConfig {
+            name: "golden".to_string(),
+            rdesc: vec![0x05, 0x01, 0xc0],
+            bus: Bus::BLUETOOTH,
+            vendor: 0x1234,
+            product: 0x4321,
+            version: 0x0102,
+            country: 0x21,
+            ..DeviceConfig::default()
+        })
+        .unwrap();
+
+        /* struct uhid_event with struct uhid_create2_req, both packed */
+        let mut expected = vec![11, 0, 0, 0];
+        expected.extend_from_slice(b"golden");
+        expected.resize(4 + 128, 0);                           // name
+        expected.resize(4 + 128 + 64, 0);                      // phys
+        expected.resize(4 + 128 + 64 + 64, 0);                 // uniq
+        expected.extend_from_slice(&[0x03, 0x00]);             // rd_size
+        expected.extend_from_slice(&[0x05, 0x00]);             // bus
+        expected.extend_from_slice(&[0x34, 0x12, 0x00, 0x00]); // vendor
+        expected.extend_from_slice(&[0x21, 0x43, 0x00, 0x00]); // product
+        expected.extend_from_slice(&[0x02, 0x01, 0x00, 0x00]); // version
+        expected.extend_from_slice(&[0x21, 0x00, 0x00, 0x00]); // country
+        expected.extend_from_slice(&[0x05, 0x01, 0xc0]);       // rd_data
+        expected.resize(4 + 4372, 0);
+
+        assert_eq!(mock.written(), [expected]);
+    }
+
+    #[test]
+    fn create_and_wait() {
+        let config = DeviceConfig {
+            name: "wait".to_string(),
+            rdesc: MOUSE_RDEC.to_vec(),
+            ..DeviceConfig::default()
+        };
+
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        mock.inject(&Event::Start { dev_flags: StartFlags::empty() });
+        dev.create_and_wait(&config, Duration::from_secs(1)).unwrap();
+
+        let mut dev = Device::with_transport(MockTransport::new());
+        let start = Instant::now();
+        match dev.create_and_wait(&config, Duration::from_millis(50)) {
+            Err(UhidError::Timeout) => assert!(start.elapsed() >= Duration::from_millis(50)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn create_confirmed() {
+        let config = DeviceConfig {
+            name: "confirmed".to_string(),
+            rdesc: MOUSE_RDEC.to_vec(),
+            ..DeviceConfig::default()
+        };
+
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        mock.inject(&Event::Output { data: vec![0x01], rtype: ReportType::Output });
+        mock.inject(&Event::Start { dev_flags: StartFlags::empty() });
+        dev.create_confirmed(&config, Duration::from_secs(1)).unwrap();
+        assert!(dev.is_created());
+
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        mock.inject(&Event::Start { dev_flags: StartFlags::empty() });
+        mock.inject(&Event::Open);
+        mock.inject(&Event::Stop);
+        match dev.create_confirmed(&config, Duration::from_secs(1)) {
+            Err(UhidError::CreateRejected) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(mock.written().len(), 2);
+        assert!(!dev.is_created());
+
+        /* rejected devices can be created again */
+        mock.inject(&Event::Start { dev_flags: StartFlags::empty() });
+        dev.create_confirmed(&config, Duration::from_secs(1)).unwrap();
+
+        let mut dev = Device::with_transport(MockTransport::new());
+        match dev.create_confirmed(&config, Duration::from_millis(10)) {
+            Err(UhidError::Timeout) => assert!(dev.is_created()),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bus_names() {
+        for (bus, name) in [
+            (Bus::PCI, "pci"),
+            (Bus::ISAPNP, "isapnp"),
+            (Bus::USB, "usb"),
+            (Bus::HIL, "hil"),
+            (Bus::BLUETOOTH, "bluetooth"),
+            (Bus::VIRTUAL, "virtual"),
+        ] {
+            assert_eq!(bus.to_string(), name);
+            assert_eq!(name.parse::<Bus>().unwrap(), bus);
+            assert_eq!(name.to_uppercase().parse::<Bus>().unwrap(), bus);
+        }
+
+        match "firewire".parse::<Bus>() {
+            Err(UhidError::UnknownBus(name)) => assert_eq!(name, "firewire"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn device_id() {
+        let id: DeviceId = "1234:4321".parse().unwrap();
+        assert_eq!(id, DeviceId { vendor: 0x1234, product: 0x4321 });
+        assert_eq!("0x046d:0XC52b".parse::<DeviceId>().unwrap(), DeviceId { vendor: 0x046d, product: 0xc52b });
+        assert_eq!(DeviceId { vendor: 0x46d, product: 0xc52b }.to_string(), "046d:c52b");
+
+        for s in &["", "1234", "1234:", ":4321", "12345:4321", "1234:4321:0", "+123:4321", "xyz:4321"] {
+            match s.parse::<DeviceId>() {
+                Err(UhidError::InvalidId(id)) => assert_eq!(&id, s),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn stats() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "stats", &MOUSE_RDEC, None).unwrap();
+
+        dev.input(&[0x01, 0x00, 0x00]).unwrap();
+        dev.input(&[0x00, 0x00, 0x00]).unwrap();
+        mock.inject(&Event::Output { data: vec![0x01], rtype: ReportType::Output });
+        mock.inject(&Event::GetReport { id: 1, rnum: 0, rtype: ReportType::Feature });
+        mock.inject(&Event::Open);
+        for _ in 0..3 {
+            dev.read_event().unwrap();
+        }
+
+        assert_eq!(
+            dev.stats(),
+            Stats {
+                inputs_sent: 2,
+                bytes_sent: 6,
+                outputs_received: 1,
+                get_reports: 1,
+                set_reports: 0,
+            }
+        );
+    }
+
+    #[cfg(feature = "latency-stats")]
+    #[test]
+    fn latency_stats() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "latency", &MOUSE_RDEC, None).unwrap();
+
+        /* input without a preceding output isn't a turnaround */
+        dev.input(&[0x00, 0x00, 0x00]).unwrap();
+        mock.inject(&Event::Open);
+        mock.inject(&Event::Output { data: vec![0x01], rtype: ReportType::Output });
+        dev.read_event().unwrap();
+        dev.read_event().unwrap();
+        thread::sleep(Duration::from_millis(5));
+        dev.input(&[0x01, 0x00, 0x00]).unwrap();
+        dev.input(&[0x00, 0x00, 0x00]).unwrap();
+
+        let stats = dev.latency_stats();
+        assert_eq!(stats.read.count(), 2);
+        assert_eq!(stats.turnaround.count(), 1);
+        assert!(stats.turnaround.min().unwrap() >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn drain() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        assert_eq!(dev.drain().unwrap(), 0);
+
+        mock.inject(&Event::Start { dev_flags: StartFlags::empty() });
+        mock.inject(&Event::Open);
+        mock.inject(&Event::Output { data: vec![0x01], rtype: ReportType::Output });
+        assert_eq!(dev.drain().unwrap(), 3);
+        assert_eq!(dev.drain().unwrap(), 0);
+    }
+
+    #[test]
+    fn boot_devices() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create_boot_keyboard(0x1234, 0x4321, "boot keyboard").unwrap();
+        dev.send_boot_keyboard(0x02, &[0x04, 0x05, 0, 0, 0, 0]).unwrap();
+        assert_eq!(&mock.written()[0][280..280 + 63], &presets::boot_keyboard()[..]);
+        assert_eq!(&mock.written()[1][4..], &[8, 0, 0x02, 0x00, 0x04, 0x05, 0, 0, 0, 0]);
+
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create_boot_mouse(0x1234, 0x4321, "boot mouse").unwrap();
+        dev.send_boot_mouse(0x01, -3, 127).unwrap();
+        assert_eq!(&mock.written()[1][4..], &[3, 0, 0x01, 0xfd, 0x7f]);
+    }
+
+    #[test]
+    fn well_formed() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "well formed", &MOUSE_RDEC, None).unwrap();
+        dev.input(&[0x01, 0x02, 0x03]).unwrap();
+        dev.get_report_reply(1, 0, &[0x04]).unwrap();
+        dev.set_report_reply(2, 0).unwrap();
+        dev.destroy().unwrap();
+        assert!(mock.written().iter().all(|event| is_well_formed(event)));
+
+        let mut input = mock.written()[1].clone();
+        input.push(0);
+        assert!(!is_well_formed(&input));
+        assert!(!is_well_formed(&mock.written()[0][..100]));
+        assert!(!is_well_formed(&Event::Open.to_bytes()));
+        assert!(!is_well_formed(&[12, 0]));
+    }
+
+    #[test]
+    fn legacy_create() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        let config = DeviceBuilder::new()
+            .name("legacy")
+            .rdesc(&MOUSE_RDEC)
+            .vendor(0x1234)
+            .creation_mode(CreationMode::Legacy)
+            .config();
+        dev.create_with(&config).unwrap();
+        dev.input(&[0x01, 0x02, 0x03]).unwrap();
+
+        let written = mock.written();
+        let create = &written[0];
+        assert_eq!(create.len(), LEGACY_CREATE_SIZE);
+        assert_eq!(&create[..4], &event_type::CREATE.to_le_bytes());
+        assert_eq!(&create[4..11], b"legacy\0");
+        let ptr = 4 + 256;
+        let ptr_len = std::mem::size_of::<usize>();
+        assert_ne!(&create[ptr..ptr + ptr_len], &vec![0; ptr_len][..]);
+        assert_eq!(&create[ptr + ptr_len..ptr + ptr_len + 8], &[55, 0, 3, 0, 0x34, 0x12, 0, 0]);
+
+        let input = &written[1];
+        assert_eq!(&input[..7], &[8, 0, 0, 0, 0x01, 0x02, 0x03]);
+        assert_eq!(&input[4 + MAX_DESCRIPTOR_SIZE..], &[3, 0]);
+
+        /* kernels before 3.18 send 4108-byte events, each read returns one */
+        let output = Event::Output { data: vec![0x01], rtype: ReportType::Output };
+        mock.inject_raw(Event::Start { dev_flags: StartFlags::empty() }.to_bytes()[..4108].to_vec());
+        mock.inject_raw(output.to_bytes()[..4108].to_vec());
+        assert_eq!(dev.read_event().unwrap(), Event::Start { dev_flags: StartFlags::empty() });
+        assert_eq!(dev.read_event().unwrap(), output);
+    }
+
+    #[test]
+    fn auto_create() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        let config = DeviceBuilder::new().name("auto").rdesc(&MOUSE_RDEC).creation_mode(CreationMode::Auto).config();
+
+        /* an old kernel that doesn't know Create2 */
+        mock.fail_next_write(io::Error::from_raw_os_error(libc::EOPNOTSUPP));
+        dev.create_with(&config).unwrap();
+        dev.input(&[0x01, 0x02, 0x03]).unwrap();
+        let types: Vec<_> = mock.written().iter().map(|event| event[0] as u32).collect();
+        assert_eq!(types, [event_type::CREATE, event_type::INPUT]);
+
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        mock.fail_next_write(io::Error::from_raw_os_error(libc::EOPNOTSUPP));
+        mock.fail_next_write(io::Error::from_raw_os_error(libc::EACCES));
+        match dev.create_with(&config) {
+            Err(UhidError::Create(e)) => assert_eq!(e.raw_os_error(), Some(libc::EOPNOTSUPP)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        dev.create_with(&DeviceConfig { creation_mode: CreationMode::Create2, ..config }).unwrap();
+        dev.input(&[0x01, 0x02, 0x03]).unwrap();
+        let types: Vec<_> = mock.written().iter().map(|event| event[0] as u32).collect();
+        assert_eq!(types, [event_type::CREATE2, event_type::INPUT2]);
+    }
+
+    #[test]
+    fn reset() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        let config = DeviceBuilder::new().name("reset").rdesc(&MOUSE_RDEC).version(0x0100).config();
+        dev.reset(&config).unwrap();
+        assert_eq!(dev.version(), 0x0100);
+
+        dev.reset(&DeviceConfig { version: 0x0200, ..config }).unwrap();
+        assert_eq!(dev.version(), 0x0200);
+        let written = mock.written();
+        let types: Vec<_> = written.iter().map(|event| event[0] as u32).collect();
+        assert_eq!(types, [event_type::CREATE2, event_type::DESTROY, event_type::CREATE2]);
+        assert_eq!(&written[2][272..276], &0x0200u32.to_le_bytes());
+    }
+
+    #[test]
+    fn empty_descriptor() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        match dev.create(0x1234, 0x4321, "empty", &[], None) {
+            Err(UhidError::EmptyDescriptor) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(mock.written().is_empty());
+        assert!(!dev.is_created());
+    }
+
+    #[test]
+    fn report_too_long() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "too long", &MOUSE_RDEC, None).unwrap();
+
+        match dev.input(&[0; 5000]) {
+            Err(UhidError::ReportTooLong(5000)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match dev.get_report_reply(1, 0, &[0; 70_000]) {
+            Err(UhidError::ReportTooLong(70_000)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(mock.written().len(), 1);
+
+        dev.input(&[0; MAX_DESCRIPTOR_SIZE]).unwrap();
+        assert_eq!(&mock.written()[1][4..6], &(MAX_DESCRIPTOR_SIZE as u16).to_le_bytes());
+    }
+
+    #[test]
+    fn device_gone() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "gone", &MOUSE_RDEC, None).unwrap();
+
+        mock.fail_next_write(io::Error::from(io::ErrorKind::BrokenPipe));
+        match dev.input(&[0x00, 0x00, 0x00]) {
+            Err(UhidError::DeviceGone) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(dev.state(), DeviceState::Destroyed);
+        match dev.input(&[0x00, 0x00, 0x00]) {
+            Err(UhidError::NotCreated) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(mock.written().len(), 1);
+
+        dev.create(0x1234, 0x4321, "gone", &MOUSE_RDEC, None).unwrap();
+        mock.fail_next_write(io::Error::from_raw_os_error(libc::ENODEV));
+        assert!(matches!(dev.input(&[0x00, 0x00, 0x00]), Err(UhidError::DeviceGone)));
+    }
+
+    #[test]
+    fn open_close() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "open", &MOUSE_RDEC, None).unwrap();
+        assert!(!dev.is_open());
+
+        mock.inject(&Event::Open);
+        assert_eq!(dev.read_event().unwrap(), Event::Open);
+        assert!(dev.is_open());
+
+        mock.inject(&Event::Close);
+        mock.inject(&Event::Close);
+        dev.drain().unwrap();
+        assert!(!dev.is_open());
+
+        mock.inject(&Event::Open);
+        dev.drain().unwrap();
+        dev.destroy().unwrap();
+        assert!(!dev.is_open());
+    }
+
+    #[test]
+    fn serialize_error() {
+        struct Unserializable;
+
+        impl Serialize for Unserializable {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("not today"))
+            }
+        }
+
+        match Device::<MockTransport>::event(EventType::SetReportReply, &Unserializable, &[]) {
+            Err(e @ UhidError::Serialize(_)) => assert!(e.to_string().contains("not today")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let event = Device::<MockTransport>::event(EventType::SetReportReply, &(1u32, 2u16), &[]).unwrap();
+        assert_eq!(event, [14, 0, 0, 0, 1, 0, 0, 0, 2, 0]);
+    }
+
+    #[test]
+    fn initial_report() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        let config = DeviceConfig {
+            name: "initial".to_string(),
+            rdesc: MOUSE_RDEC.to_vec(),
+            ..DeviceConfig::default()
+        };
+
+        mock.fail_write_after(1, io::Error::from_raw_os_error(libc::EIO));
+        match dev.create_with_initial_report(&config, &[0x01, 0x00, 0x00]) {
+            Err(UhidError::Input(e)) => assert_eq!(e.raw_os_error(), Some(libc::EIO)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(dev.state(), DeviceState::Uninitialized);
+        let written = mock.written();
+        assert_eq!(written.len(), 2);
+        assert_eq!(written[1], Device::<MockTransport>::event(EventType::Destroy, &(), &[]).unwrap());
+
+        dev.create_with_initial_report(&config, &[0x01, 0x00, 0x00]).unwrap();
+        assert!(dev.is_created());
+        assert_eq!(&mock.written()[3][..9], &[12, 0, 0, 0, 3, 0, 0x01, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn event_reader() {
+        let mock = MockTransport::new();
+        let (sender, receiver) = mpsc::channel();
+        let handle = Device::with_transport(mock.clone()).spawn_event_reader(sender);
+
+        let events = [
+            Event::Start { dev_flags: StartFlags::empty() },
+            Event::Open,
+            Event::Output { data: vec![0x01], rtype: ReportType::Output },
+            Event::Stop,
+        ];
+        for event in &events {
+            mock.inject(event);
+        }
+        mock.inject(&Event::Close);
+
+        handle.join().unwrap().unwrap();
+        assert_eq!(receiver.iter().collect::<Vec<_>>(), events);
+
+        let (sender, receiver) = mpsc::channel();
+        let handle = Device::with_transport(mock.clone()).spawn_event_reader(sender);
+        let mut unknown = vec![0; UHID_EVENT_SIZE];
+        unknown[0] = 99;
+        mock.inject_raw(unknown);
+        assert!(matches!(handle.join().unwrap(), Err(UhidError::InvalidEvent(_))));
+        assert_eq!(receiver.recv().unwrap(), Event::Close);
+    }
+
+    #[test]
+    fn error_phases() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+
+        mock.fail_next_write(io::Error::from_raw_os_error(libc::EIO));
+        match dev.create(0x1234, 0x4321, "phases", &MOUSE_RDEC, None) {
+            Err(UhidError::Create(e)) => assert_eq!(e.raw_os_error(), Some(libc::EIO)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        dev.create(0x1234, 0x4321, "phases", &MOUSE_RDEC, None).unwrap();
+        mock.fail_next_write(io::Error::from_raw_os_error(libc::EIO));
+        match dev.input(&[0x00, 0x00, 0x00]) {
+            Err(e @ UhidError::Input(_)) => assert!(e.source().is_some()),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let mut unknown = vec![0; UHID_EVENT_SIZE];
+        unknown[0] = 99;
+        mock.inject_raw(unknown);
+        assert!(matches!(dev.read_event(), Err(UhidError::InvalidEvent(_))));
+        mock.fail_next_write(io::Error::from_raw_os_error(libc::EIO));
+        assert!(matches!(dev.destroy(), Err(UhidError::Write(_))));
+    }
+
+    #[test]
+    fn replay() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "replay", &MOUSE_RDEC, None).unwrap();
+
+        let trace = vec![
+            (Duration::from_millis(20), vec![0x01, 0x00, 0x00]),
+            (Duration::from_millis(0), vec![0x00, 0x05, 0xfb]),
+            (Duration::from_millis(30), vec![0x00, 0x00, 0x00]),
+        ];
+
+        let start = Instant::now();
+        dev.replay(&trace).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let written = mock.written();
+        assert_eq!(written.len(), 4);
+        for (event, (_, report)) in written[1..].iter().zip(&trace) {
+            assert_eq!(&event[..4], &[12, 0, 0, 0]);
+            assert_eq!(&event[4..6], &[3, 0]);
+            assert_eq!(&event[6..], &report[..]);
+        }
+    }
+
+    #[test]
+    fn replay_timestamped() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "replay", &MOUSE_RDEC, None).unwrap();
+        assert_eq!(dev.replay_timestamped(&[]).unwrap(), Duration::ZERO);
+
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let trace = vec![
+            (t0, vec![0x01, 0x00, 0x00]),
+            (t0 + Duration::from_millis(30), vec![0x00, 0x05, 0xfb]),
+            (t0 + Duration::from_millis(30), vec![0x00, 0x01, 0x01]),
+            (t0 - Duration::from_millis(10), vec![0x00, 0x02, 0x02]),
+            (t0 + Duration::from_millis(50), vec![0x00, 0x00, 0x00]),
+        ];
+
+        let start = Instant::now();
+        dev.replay_timestamped(&trace).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let written = mock.written();
+        assert_eq!(written.len(), 6);
+        for (event, (_, report)) in written[1..].iter().zip(&trace) {
+            assert_eq!(&event[6..], &report[..]);
+        }
+    }
+
+    #[test]
+    fn reconnect() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        assert!(matches!(dev.reconnect(Duration::ZERO), Err(UhidError::NotCreated)));
+
+        dev.create(0x1234, 0x4321, "reconnect", &MOUSE_RDEC, Some(Bus::BLUETOOTH)).unwrap();
+        dev.reconnect_loop(3, Duration::from_millis(1)).unwrap();
+        assert!(dev.is_created());
+        assert_eq!(dev.bus(), Bus::BLUETOOTH);
+
+        let written = mock.written();
+        assert_eq!(written.len(), 7);
+        for pair in written[1..].chunks(2) {
+            assert_eq!(&pair[0][..4], &event_type::DESTROY.to_le_bytes());
+            assert_eq!(pair[1], written[0]);
+        }
+    }
+
+    #[test]
+    fn reconnect_jittered() {
+        let (base, jitter) = (Duration::from_millis(3), Duration::from_millis(2));
+        let mut pauses = Vec::new();
+        for seed in 0..200 {
+            let pause = jittered(base, jitter, seed);
+            assert!(pause >= base - jitter && pause <= base + jitter, "{:?}", pause);
+            assert_eq!(jittered(base, jitter, seed), pause);
+            pauses.push(pause);
+        }
+        pauses.sort();
+        pauses.dedup();
+        assert!(pauses.len() > 100);
+        assert!(jittered(Duration::ZERO, jitter, 7) <= jitter);
+        assert_eq!(jittered(base, Duration::ZERO, 7), base);
+
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "jitter", &MOUSE_RDEC, None).unwrap();
+        let start = Instant::now();
+        let pause = dev.reconnect_jittered(base, jitter, Some(42)).unwrap();
+        assert_eq!(pause, jittered(base, jitter, 42));
+        assert!(start.elapsed() >= pause);
+        assert_eq!(mock.written().len(), 3);
+    }
+
+    #[test]
+    fn report_lengths() {
+        let mut rdesc = descriptor::ReportDescriptor::new();
+        rdesc.collection(descriptor::Collection::Application);
+        let first = rdesc.collection_with_report_id("first", descriptor::Collection::Logical).unwrap();
+        rdesc.report_size(8).report_count(4).input(descriptor::DATA_VAR_ABS).output(descriptor::DATA_VAR_ABS);
+        rdesc.end_collection();
+        let second = rdesc.collection_with_report_id("second", descriptor::Collection::Logical).unwrap();
+        rdesc.report_size(1).report_count(12).input(descriptor::DATA_VAR_ABS);
+        rdesc.end_collection().end_collection();
+
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "lengths", &rdesc.build(), None).unwrap();
+
+        let expected: HashMap<_, _> = [(Some(first), 5), (Some(second), 3)].iter().cloned().collect();
+        assert_eq!(dev.report_lengths(), &expected);
+
+        dev.send_neutral().unwrap();
+        let written = mock.written();
+        assert_eq!(written.len(), 3);
+        assert_eq!(&written[1][4..], &[5, 0, first, 0, 0, 0, 0]);
+        assert_eq!(&written[2][4..], &[3, 0, second, 0, 0]);
+    }
+
+    #[test]
+    fn input_checked() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create_boot_mouse(0x1234, 0x4321, "checked").unwrap();
+
+        dev.input_checked(&[0x01, 0x02, 0x03]).unwrap();
+        match dev.input_checked(&[0x01, 0x02]) {
+            Err(UhidError::ReportLengthMismatch { expected: 3, got: 2 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(mock.written().len(), 2);
+
+        let mut rdesc = descriptor::ReportDescriptor::new();
+        rdesc.collection(descriptor::Collection::Application);
+        let id = rdesc.collection_with_report_id("first", descriptor::Collection::Logical).unwrap();
+        rdesc.report_size(8).report_count(2).input(descriptor::DATA_VAR_ABS);
+        rdesc.end_collection().end_collection();
+        let mut dev = Device::with_transport(MockTransport::new());
+        dev.create(0x1234, 0x4321, "checked", &rdesc.build(), None).unwrap();
+
+        dev.input_checked(&[id, 0x00, 0x00]).unwrap();
+        match dev.input_checked(&[id + 1, 0x00, 0x00]) {
+            Err(UhidError::UnknownReport(Some(unknown))) => assert_eq!(unknown, id + 1),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        /* unparsable descriptor, nothing to check against */
+        let mut dev = Device::with_transport(MockTransport::new());
+        dev.create(0x1234, 0x4321, "unchecked", &[0xc0], None).unwrap();
+        dev.input_checked(&[0x01]).unwrap();
+    }
+
+    #[test]
+    fn event_bytes() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "event bytes", &MOUSE_RDEC, None).unwrap();
+        dev.input(&[0x01, 0x02, 0x03]).unwrap();
+        dev.get_report_reply(0x0a0b0c0d, 5, &[0xaa, 0xbb]).unwrap();
+        dev.set_report_reply(7, 0).unwrap();
+        dev.destroy().unwrap();
+
+        /* type, fields and data simply concatenated */
+        let concat = |parts: &[&[u8]]| parts.concat();
+        let written = mock.written();
+        assert_eq!(written[1], concat(&[&[12, 0, 0, 0], &[3, 0], &[0x01, 0x02, 0x03]]));
+        assert_eq!(
+            written[2],
+            concat(&[&[10, 0, 0, 0], &[0x0d, 0x0c, 0x0b, 0x0a], &[5, 0], &[2, 0], &[0xaa, 0xbb]])
+        );
+        assert_eq!(written[3], concat(&[&[14, 0, 0, 0], &[7, 0, 0, 0], &[0, 0]]));
+        assert_eq!(written[4], [1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn numbered_reports() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "numbered", &MOUSE_RDEC, None).unwrap();
+        assert_eq!(dev.numbered_reports(), None);
+
+        let flags = StartFlags::NUMBERED_FEATURE_REPORTS | StartFlags::NUMBERED_INPUT_REPORTS;
+        mock.inject(&Event::Start { dev_flags: flags });
+        dev.read_event().unwrap();
+        assert_eq!(dev.numbered_reports(), Some(flags));
+        assert!(!dev.numbered_reports().unwrap().contains(StartFlags::NUMBERED_OUTPUT_REPORTS));
+
+        dev.destroy().unwrap();
+        assert_eq!(dev.numbered_reports(), None);
+    }
+
+    #[test]
+    fn type_keys() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create_boot_keyboard(0x1234, 0x4321, "type keys").unwrap();
+
+        /* "Hoo", with the repeated key */
+        let keys = [
+            presets::KeyEvent { keycode: 0x0b, modifiers: 0x02 },
+            presets::KeyEvent { keycode: 0x12, modifiers: 0 },
+            presets::KeyEvent { keycode: 0x12, modifiers: 0 },
+        ];
+        let start = Instant::now();
+        dev.type_keys(&keys, Duration::from_millis(2)).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(10));
+
+        let reports: Vec<_> = mock.written()[1..].iter().map(|event| event[6..].to_vec()).collect();
+        let release = vec![0; 8];
+        assert_eq!(
+            reports,
+            [
+                vec![0x02, 0, 0x0b, 0, 0, 0, 0, 0],
+                release.clone(),
+                vec![0x00, 0, 0x12, 0, 0, 0, 0, 0],
+                release.clone(),
+                vec![0x00, 0, 0x12, 0, 0, 0, 0, 0],
+                release,
+            ]
+        );
+    }
+
+    #[test]
+    fn type_string() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create_boot_keyboard(0x1234, 0x4321, "type string").unwrap();
+
+        dev.type_string("Hi!", Duration::ZERO).unwrap();
+        let presses: Vec<_> = mock.written()[1..].iter().step_by(2).map(|event| event[6..9].to_vec()).collect();
+        assert_eq!(presses, [[0x02, 0, 0x0b], [0x00, 0, 0x0c], [0x02, 0, 0x1e]]);
+
+        let written = mock.written().len();
+        match dev.type_string("ok né", Duration::ZERO) {
+            Err(UhidError::UnmappableChar('é')) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(mock.written().len(), written);
+    }
+
+    #[test]
+    fn type_string_fast() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create_boot_keyboard(0x1234, 0x4321, "type fast").unwrap();
+
+        let start = Instant::now();
+        dev.type_string_fast("aab", Duration::from_millis(2)).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(10));
+
+        let reports: Vec<_> = mock.written()[1..].iter().map(|event| event[6..9].to_vec()).collect();
+        let release = vec![0, 0, 0];
+        assert_eq!(
+            reports,
+            [vec![0, 0, 0x04], release.clone(), vec![0, 0, 0x04], release.clone(), vec![0, 0, 0x05], release]
+        );
+    }
+
+    #[test]
+    fn epoll_wait() {
+        use std::os::unix::net::UnixDatagram;
+
+        let mut epoll = EpollDevice::empty().unwrap();
+        let mut peers = Vec::new();
+        for _ in 0..3 {
+            let (local, peer) = UnixDatagram::pair().unwrap();
+            epoll.add(Device::from_fd(OwnedFd::from(local))).unwrap();
+            peers.push(peer);
+        }
+        assert!(epoll.wait(4, Duration::from_millis(10)).unwrap().is_empty());
+
+        peers[0].send(&Event::Open.to_bytes()).unwrap();
+        peers[2].send(&Event::Stop.to_bytes()).unwrap();
+        let mut events = epoll.wait(4, Duration::from_secs(5)).unwrap();
+        events.sort_by_key(|&(token, _)| token);
+        assert_eq!(events, [(Token(0), Event::Open), (Token(2), Event::Stop)]);
+
+        peers[1].send(&Event::Close.to_bytes()).unwrap();
+        peers[2].send(&Event::Close.to_bytes()).unwrap();
+        let mut first = epoll.wait(1, Duration::from_secs(5)).unwrap();
+        assert_eq!(first.len(), 1);
+        first.extend(epoll.wait(1, Duration::from_secs(5)).unwrap());
+        first.sort_by_key(|&(token, _)| token);
+        assert_eq!(first, [(Token(1), Event::Close), (Token(2), Event::Close)]);
+        assert!(epoll.device(Token(3)).is_none());
+    }
+
+    #[test]
+    fn poll_wait() {
+        use std::os::unix::net::UnixDatagram;
+
+        let mut poll = PollDevice::empty();
+        let mut peers = Vec::new();
+        for _ in 0..3 {
+            let (local, peer) = UnixDatagram::pair().unwrap();
+            poll.add(Device::from_fd(OwnedFd::from(local)));
+            peers.push(peer);
+        }
+        assert!(poll.wait(4, Duration::from_millis(10)).unwrap().is_empty());
+
+        peers[0].send(&Event::Open.to_bytes()).unwrap();
+        peers[2].send(&Event::Stop.to_bytes()).unwrap();
+        let events = poll.wait(4, Duration::from_secs(5)).unwrap();
+        assert_eq!(events, [(Token(0), Event::Open), (Token(2), Event::Stop)]);
+
+        peers[1].send(&Event::Close.to_bytes()).unwrap();
+        peers[2].send(&Event::Close.to_bytes()).unwrap();
+        assert_eq!(poll.wait(1, Duration::from_secs(5)).unwrap(), [(Token(1), Event::Close)]);
+        assert_eq!(poll.wait(1, Duration::from_secs(5)).unwrap(), [(Token(2), Event::Close)]);
+        assert!(poll.device(Token(3)).is_none());
+    }
+
+    #[test]
+    fn clone_handle() {
+        use std::os::unix::net::UnixDatagram;
+
+        let (local, peer) = UnixDatagram::pair().unwrap();
+        let mut reader = Device::from_fd(OwnedFd::from(local));
+        reader.create_boot_mouse(0x1234, 0x4321, "clone").unwrap();
+        reader.set_feature_report(2, &[0x02, 0x10]);
+        let mut writer = reader.try_clone_handle().unwrap();
+        assert!(writer.is_created());
+        assert_eq!(writer.name(), "clone");
+        assert_eq!(writer.feature_reports, reader.feature_reports);
+
+        let writer = thread::spawn(move || writer.send_boot_mouse(0x01, 0, 0));
+        peer.send(&Event::Open.to_bytes()).unwrap();
+        assert_eq!(reader.read_event().unwrap(), Event::Open);
+        writer.join().unwrap().unwrap();
+
+        let mut event = vec![0; UHID_EVENT_SIZE];
+        for _ in 0..2 {
+            let len = peer.recv(&mut event).unwrap();
+            event.truncate(len);
+        }
+        assert_eq!(&event[..4], &(EventType::Input2 as u32).to_le_bytes());
+        assert_eq!(&event[6..9], &[0x01, 0, 0]);
+    }
+
+    #[test]
+    fn fd_release() {
+        use std::os::unix::net::UnixStream;
+
+        let mut epoll = EpollDevice::empty().unwrap();
+        let mut poll = PollDevice::empty();
+        let mut peers = Vec::new();
+        for _ in 0..2 {
+            let (local, peer) = UnixStream::pair().unwrap();
+            let dev = Device::from_fd(OwnedFd::from(local));
+            assert_eq!(dev.fd_count(), 1);
+            poll.add(dev.try_clone_handle().unwrap());
+            epoll.add(dev).unwrap();
+            peers.push(peer);
+        }
+        assert_eq!(epoll.fd_count(), 3);
+        assert_eq!(poll.fd_count(), 2);
+
+        /* the peer only sees the end of the stream once every handle to the other end is closed */
+        drop(epoll);
+        peers[0].set_nonblocking(true).unwrap();
+        assert_eq!(peers[0].read(&mut [0]).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        drop(poll);
+        for peer in &mut peers {
+            peer.set_nonblocking(false).unwrap();
+            assert_eq!(peer.read(&mut [0]).unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn battery_level() {
+        let mut rdesc = vec![0x05, 0x01, 0x09, 0x02, 0xa1, 0x01];
+        rdesc.extend(presets::battery(2));
+        rdesc.push(0xc0);
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "battery", &rdesc, None).unwrap();
+
+        for (id, percent) in [(1, 80), (2, 150)] {
+            dev.set_battery_level(2, percent);
+            mock.inject(&Event::GetReport { id, rnum: 2, rtype: ReportType::Feature });
+            mock.inject(&Event::Close);
+            assert_eq!(dev.read_event().unwrap(), Event::Close);
+        }
+        assert_eq!(
+            mock.get_report_replies(),
+            [
+                mock::GetReportReply { id: 1, err: 0, data: vec![2, 80] },
+                mock::GetReportReply { id: 2, err: 0, data: vec![2, 100] },
+            ]
+        );
+    }
+
+    #[test]
+    fn serial_feature() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        let config = DeviceBuilder::new().name("serial").rdesc(&MOUSE_RDEC).serial("SN-0042").config();
+        dev.create_with(&config).unwrap();
+        assert_eq!(&mock.written()[0][196..204], b"SN-0042\0");
+
+        dev.set_serial_feature(3, "SN-0042");
+        mock.inject(&Event::GetReport { id: 1, rnum: 3, rtype: ReportType::Feature });
+        mock.inject(&Event::GetReport { id: 2, rnum: 4, rtype: ReportType::Feature });
+        assert_eq!(dev.read_event().unwrap(), Event::GetReport { id: 2, rnum: 4, rtype: ReportType::Feature });
+        assert_eq!(
+            mock.get_report_replies(),
+            [mock::GetReportReply { id: 1, err: 0, data: b"\x03SN-0042".to_vec() }]
+        );
+
+        let mut dev = Device::with_transport(MockTransport::new());
+        let config = DeviceConfig { uniq: "x".repeat(64), ..DeviceConfig::default() };
+        match dev.create_with(&config) {
+            Err(UhidError::StringTooLong { field: "uniq", len: 64, max: 63 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn mouse_class() {
+        assert_eq!(descriptor::descriptor_class(&MOUSE_RDEC).unwrap(), descriptor::DeviceClass::Mouse);
+    }
+
+    #[test]
+    fn fixed_strings() {
+        let fixed: [u8; 8] = str_to_fixed("phys", "abc").unwrap();
+        assert_eq!(&fixed, b"abc\0\0\0\0\0");
+        let fixed: [u8; 8] = str_to_fixed("phys", "abcdefg").unwrap();
+        assert_eq!(&fixed, b"abcdefg\0");
+
+        match str_to_fixed::<8>("uniq", "abcdefgh") {
+            Err(UhidError::StringTooLong { field: "uniq", len: 8, max: 7 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let mut dev = Device::with_transport(MockTransport::new());
+        match dev.create_with(&DeviceConfig { name: "x".repeat(128), ..DeviceConfig::default() }) {
+            Err(UhidError::InvalidNameLength(128)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let config = DeviceConfig { name: "x".repeat(127), rdesc: vec![0x05, 0x01, 0xc0], ..DeviceConfig::default() };
+        dev.create_with(&config).unwrap();
+    }
+
+    #[test]
+    fn partial_reads() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        let bytes = Event::SetReport { id: 7, rnum: 1, rtype: ReportType::Feature, data: vec![0xaa] }.to_bytes();
+        mock.inject_raw(bytes[..10].to_vec());
+        mock.inject_raw(bytes[10..].to_vec());
+        assert_eq!(
+            dev.read_event().unwrap(),
+            Event::SetReport { id: 7, rnum: 1, rtype: ReportType::Feature, data: vec![0xaa] }
+        );
+
+        let (local, mut peer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let mut dev = Device::from_fd(OwnedFd::from(local));
+        peer.write_all(&Event::Open.to_bytes()[..100]).unwrap();
+        drop(peer);
+        assert!(matches!(dev.read_event(), Err(UhidError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn event_iterators() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        assert_eq!(dev.nonblocking_events().count(), 0);
+
+        mock.inject(&Event::Open);
+        mock.inject(&Event::Close);
+        let events: Vec<_> = dev.nonblocking_events().map(Result::unwrap).collect();
+        assert_eq!(events, [Event::Open, Event::Close]);
+        assert_eq!(dev.nonblocking_events().count(), 0);
+
+        mock.inject(&Event::Open);
+        mock.inject(&Event::Stop);
+        mock.inject(&Event::Close);
+        let events: Vec<_> = dev.blocking_events().map(Result::unwrap).collect();
+        assert_eq!(events, [Event::Open, Event::Stop]);
+
+        let mut unknown = vec![0; UHID_EVENT_SIZE];
+        unknown[0] = 99;
+        mock.inject_raw(unknown);
+        let events: Vec<_> = dev.blocking_events().collect();
+        assert!(matches!(events[..], [Ok(Event::Close), Err(UhidError::InvalidEvent(_))]));
+
+        /* blocks until the event shows up */
+        let injector = mock.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            injector.inject(&Event::Stop);
+        });
+        assert_eq!(dev.blocking_events().next().unwrap().unwrap(), Event::Stop);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn closed_while_reading() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "closed", &MOUSE_RDEC, None).unwrap();
+
+        let mut reader = Device::with_transport(mock.clone());
+        let handle = thread::spawn(move || reader.read_event());
+        thread::sleep(Duration::from_millis(20));
+        mock.close();
+        match handle.join().unwrap() {
+            Err(UhidError::UnexpectedEof) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        match dev.input(&[0x00, 0x00, 0x00]) {
+            Err(UhidError::DeviceGone) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wait_for() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        let is_output = |event: &Event| matches!(event, Event::Output { .. });
+
+        mock.inject(&Event::Open);
+        mock.inject(&Event::Output { data: vec![0x01], rtype: ReportType::Output });
+        mock.inject(&Event::Close);
+        assert_eq!(
+            dev.wait_for(is_output, Duration::from_secs(5)).unwrap(),
+            Event::Output { data: vec![0x01], rtype: ReportType::Output }
+        );
+        assert!(dev.is_open());
+
+        match dev.wait_for(is_output, Duration::from_millis(10)) {
+            Err(UhidError::Timeout) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(!dev.is_open());
+    }
+
+    #[test]
+    fn run() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "run", &MOUSE_RDEC, None).unwrap();
+
+        mock.inject(&Event::Open);
+        mock.inject(&Event::GetReport { id: 1, rnum: 1, rtype: ReportType::Feature });
+        mock.inject(&Event::GetReport { id: 2, rnum: 2, rtype: ReportType::Feature });
+        mock.inject(&Event::SetReport { id: 3, rnum: 1, rtype: ReportType::Feature, data: vec![0x01] });
+        mock.inject(&Event::Stop);
+
+        let mut seen = Vec::new();
+        dev.run(|dev, event| {
+            if let Event::GetReport { id, rnum: 1, .. } = event {
+                dev.get_report_reply(id, 0, &[0x01, 0xaa])?;
+            }
+            seen.push(event);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(seen.len(), 5);
+        assert_eq!(
+            mock.get_report_replies(),
+            [
+                mock::GetReportReply { id: 1, err: 0, data: vec![0x01, 0xaa] },
+                mock::GetReportReply { id: 2, err: libc::EIO as u16, data: vec![] },
+            ]
+        );
+        assert_eq!(mock.set_report_replies(), [mock::SetReportReply { id: 3, err: libc::EIO as u16 }]);
+
+        dev.set_auto_reply(false);
+        mock.inject(&Event::GetReport { id: 4, rnum: 2, rtype: ReportType::Feature });
+        mock.inject(&Event::Stop);
+        dev.run(|_, _| Ok(())).unwrap();
+        assert_eq!(mock.get_report_replies().len(), 2);
+    }
+
+    #[test]
+    fn output_routing() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "routing", &MOUSE_RDEC, None).unwrap();
+
+        let routed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        for report_id in [1, 2] {
+            let routed = routed.clone();
+            dev.on_output(report_id, Box::new(move |data| routed.lock().unwrap().push((report_id, data.to_vec()))));
+        }
+
+        mock.inject(&Event::Start { dev_flags: StartFlags::NUMBERED_OUTPUT_REPORTS });
+        mock.inject(&Event::Output { data: vec![0x02, 0xbb], rtype: ReportType::Output });
+        mock.inject(&Event::Output { data: vec![0x01, 0xaa], rtype: ReportType::Output });
+        mock.inject(&Event::Output { data: vec![0x03, 0xcc], rtype: ReportType::Output });
+        mock.inject(&Event::Stop);
+
+        let mut seen = Vec::new();
+        dev.run(|_, event| {
+            seen.push(event);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(*routed.lock().unwrap(), [(2, vec![0x02, 0xbb]), (1, vec![0x01, 0xaa])]);
+        /* unregistered IDs go to the run() handler */
+        assert_eq!(seen[1], Event::Output { data: vec![0x03, 0xcc], rtype: ReportType::Output });
+        assert_eq!(seen.len(), 3);
+    }
+
+    #[test]
+    fn rumble() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        let config = DeviceBuilder::new().name("gamepad").rdesc(&presets::gamepad_rumble()).config();
+        dev.create_with(&config).unwrap();
+
+        let commands = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let received = commands.clone();
+        dev.on_rumble(move |command| received.lock().unwrap().push(command));
+
+        mock.inject(&Event::Start { dev_flags: StartFlags::empty() });
+        mock.inject(&Event::Output { data: vec![0xc0, 0x20], rtype: ReportType::Output });
+        mock.inject(&Event::Output { data: vec![0x00, 0x00], rtype: ReportType::Output });
+        mock.inject(&Event::Stop);
+        dev.run(|_, _| Ok(())).unwrap();
+
+        assert_eq!(
+            *commands.lock().unwrap(),
+            [presets::RumbleCommand { strong: 0xc0, weak: 0x20 }, presets::RumbleCommand { strong: 0, weak: 0 }]
+        );
+    }
+
+    #[test]
+    fn run_while() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "run while", &MOUSE_RDEC, None).unwrap();
+        let running = Arc::new(AtomicBool::new(true));
+
+        mock.inject(&Event::Open);
+        let flag = running.clone();
+        let handle = thread::spawn(move || {
+            let mut seen = Vec::new();
+            dev.run_while(&flag, |_, event| {
+                seen.push(event);
+                Ok(())
+            })
+            .map(|()| seen)
+        });
+        thread::sleep(Duration::from_millis(20));
+        running.store(false, Ordering::Release);
+        let start = Instant::now();
+        assert_eq!(handle.join().unwrap().unwrap(), [Event::Open]);
+        assert!(start.elapsed() < SHUTDOWN_POLL_INTERVAL * 2);
+        assert_eq!(mock.written().last().unwrap()[..4], (EventType::Destroy as u32).to_le_bytes());
+
+        let (sender, receiver) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = Device::with_transport(mock.clone()).spawn_event_reader_while(sender, running.clone());
+        mock.inject(&Event::Close);
+        assert_eq!(receiver.recv().unwrap(), Event::Close);
+        running.store(false, Ordering::Release);
+        handle.join().unwrap().unwrap();
+        assert_eq!(mock.written().len(), 3);
+    }
+
+    #[test]
+    fn outbound_framing() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        let config = DeviceConfig {
+            name: "golden".to_string(),
+            phys: "phys".to_string(),
+            uniq: "uniq".to_string(),
+            rdesc: vec![0x05, 0x01, 0xc0],
+            bus: Bus::BLUETOOTH,
+            vendor: 0x1234,
+            product: 0x5678,
+            version: 0x0102,
+            country: 33,
+            ..DeviceConfig::default()
+        };
+        dev.create_with(&config).unwrap();
+        dev.input(&[0xaa, 0xbb]).unwrap();
+        dev.get_report_reply(0x01020304, 5, &[0xcc]).unwrap();
+        dev.set_report_reply(0x0a0b0c0d, 0).unwrap();
+        dev.destroy().unwrap();
+
+        let written = mock.written();
+        let mut create2 = vec![0; UHID_EVENT_SIZE];
+        create2[..4].copy_from_slice(&[11, 0, 0, 0]);
+        create2[4..10].copy_from_slice(b"golden");
+        create2[132..136].copy_from_slice(b"phys");
+        create2[196..200].copy_from_slice(b"uniq");
+        create2[260..280].copy_from_slice(&[
+            3, 0, 5, 0, 0x34, 0x12, 0, 0, 0x78, 0x56, 0, 0, 0x02, 0x01, 0, 0, 33, 0, 0, 0,
+        ]);
+        create2[280..283].copy_from_slice(&[0x05, 0x01, 0xc0]);
+        assert_eq!(written[0], create2);
+        assert_eq!(written[1], [12, 0, 0, 0, 2, 0, 0xaa, 0xbb]);
+        assert_eq!(written[2], [10, 0, 0, 0, 0x04, 0x03, 0x02, 0x01, 5, 0, 1, 0, 0xcc]);
+        assert_eq!(written[3], [14, 0, 0, 0, 0x0d, 0x0c, 0x0b, 0x0a, 0, 0]);
+        assert_eq!(written[4], [1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn read_buffer_reuse() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+
+        let long = Event::Output { data: vec![0xaa; 64], rtype: ReportType::Output };
+        let short = Event::SetReport { id: 1, rnum: 0, rtype: ReportType::Feature, data: vec![0x01] };
+        mock.inject(&long);
+        mock.inject(&short);
+        let first = dev.read_event().unwrap();
+        let second = dev.read_event().unwrap();
+        assert_eq!((first, second), (long, short));
+    }
+
+    #[test]
+    fn flush() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "flush", &MOUSE_RDEC, None).unwrap();
+        dev.input(&[0x00, 0x01, 0x01]).unwrap();
+
+        /* unbuffered, the report was written before any flush */
+        assert_eq!(mock.written().len(), 2);
+        dev.flush().unwrap();
+        assert_eq!(mock.written().len(), 2);
     }
 }
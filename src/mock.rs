@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: MIT
+
+//! In-memory transport for testing code that drives a [`Device`](crate::Device).
+//!
+//! Events injected into a [`MockTransport`] are read back by the device as if the kernel had sent
+//! them, and everything the device writes is recorded for inspection.
+//!
+//! ```
+//! use uhid_rs::mock::{GetReportReply, MockTransport};
+//! use uhid_rs::{Device, Event, ReportType};
+//!
+//! let mock = MockTransport::new();
+//! let mut dev = Device::with_transport(mock.clone());
+//!
+//! mock.inject(&Event::GetReport { id: 1, rnum: 2, rtype: ReportType::Feature });
+//! if let Event::GetReport { id, .. } = dev.read_event().unwrap() {
+//!     dev.get_report_reply(id, 0, &[0x02, 0x10]).unwrap();
+//! }
+//!
+//! assert_eq!(mock.get_report_replies(), [GetReportReply { id: 1, err: 0, data: vec![0x02, 0x10] }]);
+//! ```
+
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::{Event, EventType, Transport};
+
+/// In-memory transport standing in for `/dev/uhid`.
+///
+/// Clones share the same state, so a test can keep one handle to inspect what a `Device` wrote
+/// through the other. Like a blocking file descriptor, reads wait until an event is injected.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    inner: Arc<(Mutex<Inner>, Condvar)>,
+}
+
+#[derive(Default)]
+struct Inner {
+    written: Vec<Vec<u8>>,
+    write_errors: VecDeque<io::Error>,
+    delayed_write_error: Option<(usize, io::Error)>,
+    events: VecDeque<Vec<u8>>,
+    closed: bool,
+}
+
+/// Get report reply written by the device.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GetReportReply {
+    pub id: u32,
+    pub err: u16,
+    pub data: Vec<u8>,
+}
+
+/// Set report reply written by the device.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetReportReply {
+    pub id: u32,
+    pub err: u16,
+}
+
+fn event_type(event: &[u8]) -> Option<EventType> {
+    EventType::from_u32(u32::from_le_bytes(event.get(..4)?.try_into().unwrap()))
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.0.lock().unwrap()
+    }
+
+    /// Raw events written so far, one entry per `write()` call.
+    pub fn written(&self) -> Vec<Vec<u8>> {
+        self.lock().written.clone()
+    }
+
+    /// Makes the next `write()` fail with `error` instead of recording the event.
+    pub fn fail_next_write(&self, error: io::Error) {
+        self.lock().write_errors.push_back(error);
+    }
+
+    /// Lets `writes` more writes through, then makes the one after fail with `error`.
+    pub fn fail_write_after(&self, writes: usize, error: io::Error) {
+        self.lock().delayed_write_error = Some((writes, error));
+    }
+
+    /// Queues an event for the device to read.
+    pub fn inject(&self, event: &Event) {
+        self.inject_raw(event.to_bytes());
+    }
+
+    /// Queues raw bytes to be returned by a single `read()`, or several if the buffer is smaller.
+    pub fn inject_raw(&self, bytes: Vec<u8>) {
+        self.lock().events.push_back(bytes);
+        self.inner.1.notify_all();
+    }
+
+    /// Closes the transport, like the other end of a pipe going away.
+    ///
+    /// Events already queued can still be read, after them reads return end of file instead of
+    /// blocking, and writes fail with `BrokenPipe`. A read blocked on another clone wakes up.
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.inner.1.notify_all();
+    }
+
+    /// Get report replies written so far.
+    pub fn get_report_replies(&self) -> Vec<GetReportReply> {
+        self.lock()
+            .written
+            .iter()
+            .filter(|event| event_type(event) == Some(EventType::GetReportReply))
+            .map(|event| {
+                let size = u16::from_le_bytes(event[10..12].try_into().unwrap()) as usize;
+                GetReportReply {
+                    id: u32::from_le_bytes(event[4..8].try_into().unwrap()),
+                    err: u16::from_le_bytes(event[8..10].try_into().unwrap()),
+                    data: event[12..12 + size].to_vec(),
+                }
+            })
+            .collect()
+    }
+
+    /// Set report replies written so far.
+    pub fn set_report_replies(&self) -> Vec<SetReportReply> {
+        self.lock()
+            .written
+            .iter()
+            .filter(|event| event_type(event) == Some(EventType::SetReportReply))
+            .map(|event| SetReportReply {
+                id: u32::from_le_bytes(event[4..8].try_into().unwrap()),
+                err: u16::from_le_bytes(event[8..10].try_into().unwrap()),
+            })
+            .collect()
+    }
+}
+
+impl Read for MockTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut inner = self.inner.1.wait_while(self.lock(), |inner| inner.events.is_empty() && !inner.closed).unwrap();
+        let mut event = match inner.events.pop_front() {
+            Some(event) => event,
+            None => return Ok(0),
+        };
+        let len = event.len().min(buf.len());
+        buf[..len].copy_from_slice(&event[..len]);
+        /* like a stream, keep what didn't fit for the next read */
+        if len < event.len() {
+            inner.events.push_front(event.split_off(len));
+        }
+        Ok(len)
+    }
+}
+
+impl Write for MockTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = self.lock();
+        if inner.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        if let Some(error) = inner.write_errors.pop_front() {
+            return Err(error);
+        }
+        match inner.delayed_write_error.take() {
+            Some((0, error)) => return Err(error),
+            Some((writes, error)) => inner.delayed_write_error = Some((writes - 1, error)),
+            None => (),
+        }
+        inner.written.push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for MockTransport {
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+        let condvar = &self.inner.1;
+        let empty = |inner: &mut Inner| inner.events.is_empty() && !inner.closed;
+        let inner = match timeout {
+            Some(timeout) => condvar.wait_timeout_while(self.lock(), timeout, empty).unwrap().0,
+            None => condvar.wait_while(self.lock(), empty).unwrap(),
+        };
+        /* end of file is readable, like on a closed pipe */
+        Ok(!inner.events.is_empty() || inner.closed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Device, ReportType};
+
+    #[test]
+    fn get_report() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+        dev.create(0x1234, 0x4321, "get report", &[0x05, 0x01], None).unwrap();
+
+        mock.inject(&Event::GetReport { id: 42, rnum: 3, rtype: ReportType::Feature });
+        mock.inject(&Event::GetReport { id: 43, rnum: 9, rtype: ReportType::Feature });
+
+        for _ in 0..2 {
+            match dev.read_event().unwrap() {
+                Event::GetReport { id, rnum: 3, rtype: ReportType::Feature } => {
+                    dev.get_report_reply(id, 0, &[0x03, 0xaa, 0xbb]).unwrap();
+                }
+                Event::GetReport { id, .. } => dev.get_report_reply(id, libc::EIO as u16, &[]).unwrap(),
+                event => panic!("unexpected event: {:?}", event),
+            }
+        }
+
+        assert_eq!(
+            mock.get_report_replies(),
+            [
+                GetReportReply { id: 42, err: 0, data: vec![0x03, 0xaa, 0xbb] },
+                GetReportReply { id: 43, err: libc::EIO as u16, data: vec![] },
+            ]
+        );
+    }
+
+    #[test]
+    fn set_report() {
+        let mock = MockTransport::new();
+        let mut dev = Device::with_transport(mock.clone());
+
+        mock.inject(&Event::SetReport { id: 7, rnum: 1, rtype: ReportType::Output, data: vec![0x01, 0x02] });
+        match dev.read_event().unwrap() {
+            Event::SetReport { id, data, .. } => {
+                assert_eq!(data, [0x01, 0x02]);
+                dev.set_report_reply(id, 0).unwrap();
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        assert_eq!(mock.set_report_replies(), [SetReportReply { id: 7, err: 0 }]);
+    }
+}
@@ -0,0 +1,308 @@
+// SPDX-License-Identifier: MIT
+
+use std::fs::File;
+
+use crate::{presets, Device, DeviceConfig, Event, ReportType, Transport, UhidError};
+
+/// Largest coordinate of an absolute [`Mouse`], the logical maximum of [`presets::absolute_mouse`].
+pub const ABSOLUTE_MAX: u16 = 32767;
+
+/// Wheel units per notch of a [`MouseMode::HighResolution`] mouse once the host enabled the
+/// Resolution Multiplier, the unit of [`Mouse::scroll_hires`].
+pub const HIRES_WHEEL_MULTIPLIER: i32 = 8;
+
+/// How a [`Mouse`] reports movement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseMode {
+    /// Movement relative to the last report, with [`presets::wheel_mouse`].
+    Relative,
+    /// Position on the screen, with [`presets::absolute_mouse`].
+    Absolute,
+    /// Like [`Relative`](Self::Relative), with the high-resolution wheel of
+    /// [`presets::mouse_hires`].
+    HighResolution,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+impl MouseButton {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Takes the part of `remaining` that fits in a signed byte report field.
+fn step(remaining: &mut i32) -> i32 {
+    step_within(remaining, 127)
+}
+
+fn step_within(remaining: &mut i32, max: i32) -> i32 {
+    let step = (*remaining).clamp(-max, max);
+    *remaining -= step;
+    step
+}
+
+/// Mouse that keeps track of its buttons, so every report carries the right button state.
+pub struct Mouse<T = File> {
+    device: Device<T>,
+    mode: MouseMode,
+    buttons: u8,
+    position: (u16, u16),
+    /* Resolution Multiplier set by the host, high-resolution mode only */
+    hires: bool,
+    /* scroll_hires() amount that didn't make a whole wheel unit yet */
+    wheel_remainder: i32,
+}
+
+impl<T: Transport> Mouse<T> {
+    /// Creates the device with `config`, replacing its report descriptor with the one for `mode`.
+    pub fn create(mut device: Device<T>, mode: MouseMode, config: &DeviceConfig) -> Result<Self, UhidError> {
+        let rdesc = match mode {
+            MouseMode::Relative => presets::wheel_mouse(),
+            MouseMode::Absolute => presets::absolute_mouse(),
+            MouseMode::HighResolution => presets::mouse_hires(),
+        };
+        device.create_with(&DeviceConfig { rdesc, ..config.clone() })?;
+        Ok(Mouse { device, mode, buttons: 0, position: (0, 0), hires: false, wheel_remainder: 0 })
+    }
+
+    pub fn device(&mut self) -> &mut Device<T> {
+        &mut self.device
+    }
+
+    pub fn into_device(self) -> Device<T> {
+        self.device
+    }
+
+    pub fn mode(&self) -> MouseMode {
+        self.mode
+    }
+
+    pub fn is_pressed(&self, button: MouseButton) -> bool {
+        self.buttons & button.bit() != 0
+    }
+
+    /// Whether the host enabled the Resolution Multiplier, always false outside high-resolution
+    /// mode. See [`handle_event`](Self::handle_event).
+    pub fn is_hires_enabled(&self) -> bool {
+        self.hires
+    }
+
+    fn send(&mut self, dx: i32, dy: i32, wheel: i32) -> Result<(), UhidError> {
+        let (dx, dy) = (dx as i8 as u8, dy as i8 as u8);
+        match self.mode {
+            MouseMode::Relative => self.device.input(&[self.buttons, dx, dy, wheel as i8 as u8]),
+            MouseMode::Absolute => {
+                let (x, y) = (self.position.0.to_le_bytes(), self.position.1.to_le_bytes());
+                self.device.input(&[self.buttons, x[0], x[1], y[0], y[1], wheel as i8 as u8])
+            }
+            MouseMode::HighResolution => {
+                let wheel = (wheel as i16).to_le_bytes();
+                self.device.input(&[self.buttons, dx, dy, wheel[0], wheel[1]])
+            }
+        }
+    }
+
+    /// Sends `units` of wheel movement, split over several reports if they don't fit in one.
+    fn send_wheel(&mut self, mut units: i32) -> Result<(), UhidError> {
+        let max = match self.mode {
+            MouseMode::HighResolution => i16::MAX as i32,
+            _ => 127,
+        };
+        while units != 0 {
+            let step = step_within(&mut units, max);
+            self.send(0, 0, step)?;
+        }
+        Ok(())
+    }
+
+    /// Moves the pointer by `dx`, `dy`.
+    ///
+    /// In relative mode, movement beyond the -127..=127 range of a report is split over several
+    /// reports. In absolute mode, the pointer moves from its last position, stopping at the edges.
+    pub fn move_relative(&mut self, mut dx: i32, mut dy: i32) -> Result<(), UhidError> {
+        if self.mode == MouseMode::Absolute {
+            let x = (self.position.0 as i32).saturating_add(dx).clamp(0, ABSOLUTE_MAX as i32);
+            let y = (self.position.1 as i32).saturating_add(dy).clamp(0, ABSOLUTE_MAX as i32);
+            return self.move_absolute(x as u16, y as u16);
+        }
+
+        loop {
+            let (x, y) = (step(&mut dx), step(&mut dy));
+            self.send(x, y, 0)?;
+            if dx == 0 && dy == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Moves the pointer to `x`, `y`, clamped to [`ABSOLUTE_MAX`]. Only supported in absolute mode.
+    pub fn move_absolute(&mut self, x: u16, y: u16) -> Result<(), UhidError> {
+        if self.mode != MouseMode::Absolute {
+            return Err(UhidError::Unsupported("absolute movement on a relative mouse"));
+        }
+        self.position = (x.min(ABSOLUTE_MAX), y.min(ABSOLUTE_MAX));
+        self.send(0, 0, 0)
+    }
+
+    pub fn press(&mut self, button: MouseButton) -> Result<(), UhidError> {
+        self.buttons |= button.bit();
+        self.send(0, 0, 0)
+    }
+
+    pub fn release(&mut self, button: MouseButton) -> Result<(), UhidError> {
+        self.buttons &= !button.bit();
+        self.send(0, 0, 0)
+    }
+
+    /// Presses and releases `button`.
+    pub fn click(&mut self, button: MouseButton) -> Result<(), UhidError> {
+        self.press(button)?;
+        self.release(button)
+    }
+
+    /// Turns the wheel by `amount` notches, positive is away from the user.
+    pub fn scroll(&mut self, amount: i32) -> Result<(), UhidError> {
+        if self.hires {
+            self.send_wheel(amount.saturating_mul(HIRES_WHEEL_MULTIPLIER))
+        } else {
+            self.send_wheel(amount)
+        }
+    }
+
+    /// Turns the wheel by `amount` eighths of a notch, see [`HIRES_WHEEL_MULTIPLIER`].
+    ///
+    /// Fractions of a notch are only sent once the host enabled the Resolution Multiplier of a
+    /// high-resolution mouse. Otherwise they add up until they make a whole notch, which is sent
+    /// then, the rest being kept for the next call.
+    pub fn scroll_hires(&mut self, amount: i16) -> Result<(), UhidError> {
+        if self.hires {
+            return self.send_wheel(amount as i32);
+        }
+        self.wheel_remainder += amount as i32;
+        let notches = self.wheel_remainder / HIRES_WHEEL_MULTIPLIER;
+        self.wheel_remainder -= notches * HIRES_WHEEL_MULTIPLIER;
+        self.send_wheel(notches)
+    }
+
+    /// Keeps track of the Resolution Multiplier if `event` is a request for the feature report of
+    /// a high-resolution mouse, answering it, returns whether it was one.
+    pub fn handle_event(&mut self, event: &Event) -> Result<bool, UhidError> {
+        if self.mode != MouseMode::HighResolution {
+            return Ok(false);
+        }
+        match event {
+            Event::SetReport { id, rtype: ReportType::Feature, data, .. } if !data.is_empty() => {
+                self.hires = data[0] & 1 != 0;
+                self.wheel_remainder = 0;
+                self.device.set_report_reply(*id, 0)?;
+                Ok(true)
+            }
+            Event::GetReport { id, rtype: ReportType::Feature, .. } => {
+                self.device.get_report_reply(*id, 0, &[self.hires as u8])?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTransport;
+
+    fn mouse(mode: MouseMode) -> (MockTransport, Mouse<MockTransport>) {
+        let mock = MockTransport::new();
+        let config = DeviceConfig { name: "mouse".to_string(), ..DeviceConfig::default() };
+        let mouse = Mouse::create(Device::with_transport(mock.clone()), mode, &config).unwrap();
+        (mock, mouse)
+    }
+
+    fn reports(mock: &MockTransport) -> Vec<Vec<u8>> {
+        mock.written()[1..].iter().map(|event| event[6..].to_vec()).collect()
+    }
+
+    #[test]
+    fn click() {
+        let (mock, mut mouse) = mouse(MouseMode::Relative);
+        let rdesc = presets::wheel_mouse();
+        assert_eq!(mock.written()[0][280..280 + rdesc.len()], rdesc[..]);
+
+        mouse.press(MouseButton::Right).unwrap();
+        mouse.click(MouseButton::Left).unwrap();
+        assert!(mouse.is_pressed(MouseButton::Right));
+        mouse.release(MouseButton::Right).unwrap();
+        assert_eq!(reports(&mock), [[0x02, 0, 0, 0], [0x03, 0, 0, 0], [0x02, 0, 0, 0], [0x00, 0, 0, 0]]);
+    }
+
+    #[test]
+    fn relative() {
+        let (mock, mut mouse) = mouse(MouseMode::Relative);
+        mouse.move_relative(200, -3).unwrap();
+        mouse.scroll(-1).unwrap();
+        assert_eq!(reports(&mock), [[0x00, 127, 0xfd, 0], [0x00, 73, 0, 0], [0x00, 0, 0, 0xff]]);
+
+        match mouse.move_absolute(10, 10) {
+            Err(UhidError::Unsupported(_)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn absolute() {
+        let (mock, mut mouse) = mouse(MouseMode::Absolute);
+        mouse.move_absolute(0x1234, 40_000).unwrap();
+        mouse.move_relative(-0x2000, -1).unwrap();
+        mouse.click(MouseButton::Middle).unwrap();
+        assert_eq!(
+            reports(&mock),
+            [
+                [0x00, 0x34, 0x12, 0xff, 0x7f, 0],
+                [0x00, 0x00, 0x00, 0xfe, 0x7f, 0],
+                [0x04, 0x00, 0x00, 0xfe, 0x7f, 0],
+                [0x00, 0x00, 0x00, 0xfe, 0x7f, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn hires() {
+        let (mock, mut mouse) = mouse(MouseMode::HighResolution);
+        /* eighths of a notch add up until the host enables the multiplier */
+        mouse.scroll_hires(5).unwrap();
+        mouse.scroll_hires(5).unwrap();
+        mouse.scroll(-1).unwrap();
+        assert!(!mouse.is_hires_enabled());
+
+        let event = Event::SetReport { id: 3, rnum: 0, rtype: ReportType::Feature, data: vec![0x01] };
+        assert!(mouse.handle_event(&event).unwrap());
+        assert!(mouse.is_hires_enabled());
+        assert_eq!(mock.set_report_replies(), [crate::mock::SetReportReply { id: 3, err: 0 }]);
+        let event = Event::GetReport { id: 4, rnum: 0, rtype: ReportType::Feature };
+        assert!(mouse.handle_event(&event).unwrap());
+        assert_eq!(mock.get_report_replies()[0].data, [0x01]);
+
+        mouse.scroll_hires(-3).unwrap();
+        mouse.scroll(2).unwrap();
+        mouse.move_relative(-2, 1).unwrap();
+        let input2 = crate::event_type::INPUT2.to_le_bytes();
+        let reports: Vec<_> =
+            mock.written().iter().filter(|event| event[..4] == input2).map(|event| event[6..].to_vec()).collect();
+        assert_eq!(
+            reports,
+            [
+                [0x00, 0, 0, 0x01, 0x00],
+                [0x00, 0, 0, 0xff, 0xff],
+                [0x00, 0, 0, 0xfd, 0xff],
+                [0x00, 0, 0, 0x10, 0x00],
+                [0x00, 0xfe, 0x01, 0, 0],
+            ]
+        );
+    }
+}
@@ -0,0 +1,954 @@
+// SPDX-License-Identifier: MIT
+
+//! Report descriptors for common devices, along with their report layouts.
+//!
+//! # Boot and report protocol
+//!
+//! USB keyboards and mice can be switched by the host between the report protocol, where reports
+//! follow the report descriptor, and the boot protocol, a fixed layout simple enough for BIOSes
+//! to use without parsing descriptors. The switch is a `SET_PROTOCOL` class request handled by
+//! the USB transport, uhid has no event for it: the kernel always uses the report protocol with
+//! uhid devices, and firmware never sees them.
+//!
+//! [`boot_keyboard`] and [`boot_mouse`] declare the boot layout as their report descriptor, so
+//! their reports are correct under both protocols. For composite devices whose keyboard uses a
+//! report ID, [`KeyboardReport`] encodes the same report for either protocol.
+
+use crate::report::encode_i8_clamped;
+use crate::usages::consumer;
+
+/// Boot protocol keyboard, from appendix B.1 of the HID specification.
+///
+/// The input report is 8 bytes: a bitmap of the modifier keys (bit 0 is Left Control through bit 7
+/// Right GUI), a reserved byte and up to 6 pressed key codes, with unused slots set to 0. The output
+/// report is a single byte with the LED states (bit 0 Num Lock, 1 Caps Lock, 2 Scroll Lock,
+/// 3 Compose, 4 Kana).
+pub fn boot_keyboard() -> Vec<u8> {
+    vec![
+        0x05, 0x01,  // Usage Page (Generic Desktop)        0
+        0x09, 0x06,  // Usage (Keyboard)                    2
+        0xa1, 0x01,  // Collection (Application)            4
+        0x75, 0x01,  // .Report Size (1)                    6
+        0x95, 0x08,  // .Report Count (8)                   8
+        0x05, 0x07,  // .Usage Page (Keyboard)              10
+        0x19, 0xe0,  // .Usage Minimum (224)                12
+        0x29, 0xe7,  // .Usage Maximum (231)                14
+        0x15, 0x00,  // .Logical Minimum (0)                16
+        0x25, 0x01,  // .Logical Maximum (1)                18
+        0x81, 0x02,  // .Input (Data,Var,Abs)               20
+        0x95, 0x01,  // .Report Count (1)                   22
+        0x75, 0x08,  // .Report Size (8)                    24
+        0x81, 0x01,  // .Input (Cnst,Arr,Abs)               26
+        0x95, 0x05,  // .Report Count (5)                   28
+        0x75, 0x01,  // .Report Size (1)                    30
+        0x05, 0x08,  // .Usage Page (LEDs)                  32
+        0x19, 0x01,  // .Usage Minimum (1)                  34
+        0x29, 0x05,  // .Usage Maximum (5)                  36
+        0x91, 0x02,  // .Output (Data,Var,Abs)              38
+        0x95, 0x01,  // .Report Count (1)                   40
+        0x75, 0x03,  // .Report Size (3)                    42
+        0x91, 0x01,  // .Output (Cnst,Arr,Abs)              44
+        0x95, 0x06,  // .Report Count (6)                   46
+        0x75, 0x08,  // .Report Size (8)                    48
+        0x15, 0x00,  // .Logical Minimum (0)                50
+        0x25, 0x65,  // .Logical Maximum (101)              52
+        0x05, 0x07,  // .Usage Page (Keyboard)              54
+        0x19, 0x00,  // .Usage Minimum (0)                  56
+        0x29, 0x65,  // .Usage Maximum (101)                58
+        0x81, 0x00,  // .Input (Data,Arr,Abs)               60
+        0xc0,        // End Collection                      62
+    ]
+}
+
+/// Boot or report protocol, see the [module documentation](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Boot,
+    Report,
+}
+
+/// Keyboard input report in the boot layout of [`boot_keyboard`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyboardReport {
+    /// Bitmap of the modifier keys, bit 0 is Left Control through bit 7 Right GUI.
+    pub modifiers: u8,
+    /// Pressed key codes, unused slots set to 0.
+    pub keys: [u8; 6],
+}
+
+impl KeyboardReport {
+    /// Encodes the report for `protocol`.
+    ///
+    /// Boot protocol reports never carry a report ID. Under the report protocol, `report_id` is
+    /// the ID the keyboard collection uses in the descriptor, if any.
+    pub fn to_bytes(&self, protocol: Protocol, report_id: Option<u8>) -> Vec<u8> {
+        let mut report = Vec::with_capacity(9);
+        if let (Protocol::Report, Some(id)) = (protocol, report_id) {
+            report.push(id);
+        }
+        report.push(self.modifiers);
+        report.push(0);
+        report.extend_from_slice(&self.keys);
+        report
+    }
+}
+
+/// Key press on a [`boot_keyboard`], see [`Device::type_keys`](crate::Device::type_keys).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// Keyboard page usage of the key.
+    pub keycode: u8,
+    /// Modifier bitmap held down with the key.
+    pub modifiers: u8,
+}
+
+/// Boot protocol mouse, from appendix B.2 of the HID specification.
+///
+/// The input report is 3 bytes: a bitmap of buttons 1 to 3 in the low bits, then the relative X and
+/// Y movement as signed bytes in the -127..=127 range.
+pub fn boot_mouse() -> Vec<u8> {
+    vec![
+        0x05, 0x01,  // Usage Page (Generic Desktop)        0
+        0x09, 0x02,  // Usage (Mouse)                       2
+        0xa1, 0x01,  // Collection (Application)            4
+        0x09, 0x01,  // .Usage (Pointer)                    6
+        0xa1, 0x00,  // .Collection (Physical)              8
+        0x95, 0x03,  // ..Report Count (3)                  10
+        0x75, 0x01,  // ..Report Size (1)                   12
+        0x05, 0x09,  // ..Usage Page (Button)               14
+        0x19, 0x01,  // ..Usage Minimum (1)                 16
+        0x29, 0x03,  // ..Usage Maximum (3)                 18
+        0x15, 0x00,  // ..Logical Minimum (0)               20
+        0x25, 0x01,  // ..Logical Maximum (1)               22
+        0x81, 0x02,  // ..Input (Data,Var,Abs)              24
+        0x95, 0x01,  // ..Report Count (1)                  26
+        0x75, 0x05,  // ..Report Size (5)                   28
+        0x81, 0x01,  // ..Input (Cnst,Arr,Abs)              30
+        0x75, 0x08,  // ..Report Size (8)                   32
+        0x95, 0x02,  // ..Report Count (2)                  34
+        0x05, 0x01,  // ..Usage Page (Generic Desktop)      36
+        0x09, 0x30,  // ..Usage (X)                         38
+        0x09, 0x31,  // ..Usage (Y)                         40
+        0x15, 0x81,  // ..Logical Minimum (-127)            42
+        0x25, 0x7f,  // ..Logical Maximum (127)             44
+        0x81, 0x06,  // ..Input (Data,Var,Rel)              46
+        0xc0,        // .End Collection                     48
+        0xc0,        // End Collection                      49
+    ]
+}
+
+/// Input report of the [`boot_mouse`] device.
+///
+/// Movement is given as `i32` and clamped to the -127..=127 range of the descriptor when encoded,
+/// rather than wrapping around.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MouseReport {
+    /// Bitmap of buttons 1 to 3.
+    pub buttons: u8,
+    pub dx: i32,
+    pub dy: i32,
+}
+
+impl MouseReport {
+    pub fn to_bytes(&self) -> [u8; 3] {
+        [self.buttons & 0x07, encode_i8_clamped(self.dx), encode_i8_clamped(self.dy)]
+    }
+}
+
+/// Three-button mouse with a scroll wheel.
+///
+/// The input report is 4 bytes: the buttons like [`boot_mouse`], then the relative X and Y
+/// movement and the wheel as signed bytes in the -127..=127 range.
+pub fn wheel_mouse() -> Vec<u8> {
+    vec![
+        0x05, 0x01,  // Usage Page (Generic Desktop)        0
+        0x09, 0x02,  // Usage (Mouse)                       2
+        0xa1, 0x01,  // Collection (Application)            4
+        0x09, 0x01,  // .Usage (Pointer)                    6
+        0xa1, 0x00,  // .Collection (Physical)              8
+        0x95, 0x03,  // ..Report Count (3)                  10
+        0x75, 0x01,  // ..Report Size (1)                   12
+        0x05, 0x09,  // ..Usage Page (Button)               14
+        0x19, 0x01,  // ..Usage Minimum (1)                 16
+        0x29, 0x03,  // ..Usage Maximum (3)                 18
+        0x15, 0x00,  // ..Logical Minimum (0)               20
+        0x25, 0x01,  // ..Logical Maximum (1)               22
+        0x81, 0x02,  // ..Input (Data,Var,Abs)              24
+        0x95, 0x01,  // ..Report Count (1)                  26
+        0x75, 0x05,  // ..Report Size (5)                   28
+        0x81, 0x01,  // ..Input (Cnst,Arr,Abs)              30
+        0x75, 0x08,  // ..Report Size (8)                   32
+        0x95, 0x03,  // ..Report Count (3)                  34
+        0x05, 0x01,  // ..Usage Page (Generic Desktop)      36
+        0x09, 0x30,  // ..Usage (X)                         38
+        0x09, 0x31,  // ..Usage (Y)                         40
+        0x09, 0x38,  // ..Usage (Wheel)                     42
+        0x15, 0x81,  // ..Logical Minimum (-127)            44
+        0x25, 0x7f,  // ..Logical Maximum (127)             46
+        0x81, 0x06,  // ..Input (Data,Var,Rel)              48
+        0xc0,        // .End Collection                     50
+        0xc0,        // End Collection                      51
+    ]
+}
+
+/// Three-button mouse reporting absolute coordinates, like the tablets of virtual machines.
+///
+/// The input report is 6 bytes: the buttons like [`boot_mouse`], the X and Y position as 16-bit
+/// little-endian values in the 0..=32767 range, scaled by the host to the screen, then the
+/// relative wheel movement as a signed byte.
+pub fn absolute_mouse() -> Vec<u8> {
+    vec![
+        0x05, 0x01,        // Usage Page (Generic Desktop)        0
+        0x09, 0x02,        // Usage (Mouse)                       2
+        0xa1, 0x01,        // Collection (Application)            4
+        0x09, 0x01,        // .Usage (Pointer)                    6
+        0xa1, 0x00,        // .Collection (Physical)              8
+        0x95, 0x03,        // ..Report Count (3)                  10
+        0x75, 0x01,        // ..Report Size (1)                   12
+        0x05, 0x09,        // ..Usage Page (Button)               14
+        0x19, 0x01,        // ..Usage Minimum (1)                 16
+        0x29, 0x03,        // ..Usage Maximum (3)                 18
+        0x15, 0x00,        // ..Logical Minimum (0)               20
+        0x25, 0x01,        // ..Logical Maximum (1)               22
+        0x81, 0x02,        // ..Input (Data,Var,Abs)              24
+        0x95, 0x01,        // ..Report Count (1)                  26
+        0x75, 0x05,        // ..Report Size (5)                   28
+        0x81, 0x01,        // ..Input (Cnst,Arr,Abs)              30
+        0x05, 0x01,        // ..Usage Page (Generic Desktop)      32
+        0x09, 0x30,        // ..Usage (X)                         34
+        0x09, 0x31,        // ..Usage (Y)                         36
+        0x15, 0x00,        // ..Logical Minimum (0)               38
+        0x26, 0xff, 0x7f,  // ..Logical Maximum (32767)           40
+        0x75, 0x10,        // ..Report Size (16)                  43
+        0x95, 0x02,        // ..Report Count (2)                  45
+        0x81, 0x02,        // ..Input (Data,Var,Abs)              47
+        0x09, 0x38,        // ..Usage (Wheel)                     49
+        0x15, 0x81,        // ..Logical Minimum (-127)            51
+        0x25, 0x7f,        // ..Logical Maximum (127)             53
+        0x75, 0x08,        // ..Report Size (8)                   55
+        0x95, 0x01,        // ..Report Count (1)                  57
+        0x81, 0x06,        // ..Input (Data,Var,Rel)              59
+        0xc0,              // .End Collection                     61
+        0xc0,              // End Collection                      62
+    ]
+}
+
+/// Three-button mouse with a high-resolution wheel, like [`wheel_mouse`] with a 16-bit wheel.
+///
+/// The input report is 5 bytes: the buttons like [`boot_mouse`], the relative X and Y movement as
+/// signed bytes, then the wheel as a 16-bit little-endian value in the -32767..=32767 range. The
+/// 1-byte feature report holds the Resolution Multiplier in bit 0: while the host leaves it at 0,
+/// a wheel unit is a notch, once it sets it to 1 a notch is 8 units. Linux sets it when the device
+/// is connected.
+pub fn mouse_hires() -> Vec<u8> {
+    vec![
+        0x05, 0x01,        // Usage Page (Generic Desktop)        0
+        0x09, 0x02,        // Usage (Mouse)                       2
+        0xa1, 0x01,        // Collection (Application)            4
+        0x09, 0x01,        // .Usage (Pointer)                    6
+        0xa1, 0x00,        // .Collection (Physical)              8
+        0x95, 0x03,        // ..Report Count (3)                  10
+        0x75, 0x01,        // ..Report Size (1)                   12
+        0x05, 0x09,        // ..Usage Page (Button)               14
+        0x19, 0x01,        // ..Usage Minimum (1)                 16
+        0x29, 0x03,        // ..Usage Maximum (3)                 18
+        0x15, 0x00,        // ..Logical Minimum (0)               20
+        0x25, 0x01,        // ..Logical Maximum (1)               22
+        0x81, 0x02,        // ..Input (Data,Var,Abs)              24
+        0x95, 0x01,        // ..Report Count (1)                  26
+        0x75, 0x05,        // ..Report Size (5)                   28
+        0x81, 0x01,        // ..Input (Cnst,Arr,Abs)              30
+        0x05, 0x01,        // ..Usage Page (Generic Desktop)      32
+        0x09, 0x30,        // ..Usage (X)                         34
+        0x09, 0x31,        // ..Usage (Y)                         36
+        0x15, 0x81,        // ..Logical Minimum (-127)            38
+        0x25, 0x7f,        // ..Logical Maximum (127)             40
+        0x75, 0x08,        // ..Report Size (8)                   42
+        0x95, 0x02,        // ..Report Count (2)                  44
+        0x81, 0x06,        // ..Input (Data,Var,Rel)              46
+        0xa1, 0x02,        // ..Collection (Logical)              48
+        0x09, 0x48,        // ...Usage (Resolution Multiplier)    50
+        0x15, 0x00,        // ...Logical Minimum (0)              52
+        0x25, 0x01,        // ...Logical Maximum (1)              54
+        0x35, 0x01,        // ...Physical Minimum (1)             56
+        0x45, 0x08,        // ...Physical Maximum (8)             58
+        0x75, 0x02,        // ...Report Size (2)                  60
+        0x95, 0x01,        // ...Report Count (1)                 62
+        0xb1, 0x02,        // ...Feature (Data,Var,Abs)           64
+        0x75, 0x06,        // ...Report Size (6)                  66
+        0xb1, 0x01,        // ...Feature (Cnst,Arr,Abs)           68
+        0x09, 0x38,        // ...Usage (Wheel)                    70
+        0x35, 0x00,        // ...Physical Minimum (0)             72
+        0x45, 0x00,        // ...Physical Maximum (0)             74
+        0x16, 0x01, 0x80,  // ...Logical Minimum (-32767)         76
+        0x26, 0xff, 0x7f,  // ...Logical Maximum (32767)          79
+        0x75, 0x10,        // ...Report Size (16)                 82
+        0x81, 0x06,        // ...Input (Data,Var,Rel)             84
+        0xc0,              // ..End Collection                    86
+        0xc0,              // .End Collection                     87
+        0xc0,              // End Collection                      88
+    ]
+}
+
+/// Consumer control device (media keys).
+///
+/// The input report is a single 16-bit little-endian Consumer page usage, see [`ConsumerReport`].
+pub fn consumer_control() -> Vec<u8> {
+    vec![
+        0x05, 0x0c,        // Usage Page (Consumer)              0
+        0x09, 0x01,        // Usage (Consumer Control)           2
+        0xa1, 0x01,        // Collection (Application)           4
+        0x15, 0x00,        // .Logical Minimum (0)               6
+        0x26, 0xff, 0x03,  // .Logical Maximum (1023)            8
+        0x19, 0x00,        // .Usage Minimum (0)                 11
+        0x2a, 0xff, 0x03,  // .Usage Maximum (1023)              13
+        0x75, 0x10,        // .Report Size (16)                  16
+        0x95, 0x01,        // .Report Count (1)                  18
+        0x81, 0x00,        // .Input (Data,Arr,Abs)              20
+        0xc0,              // End Collection                     22
+    ]
+}
+
+/// Input report of the [`consumer_control`] device.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConsumerReport {
+    /// Pressed Consumer page usage, `0` when nothing is pressed.
+    pub usage: u16,
+}
+
+impl ConsumerReport {
+    pub const PLAY_PAUSE: u16 = consumer::PLAY_PAUSE.0;
+    pub const MUTE: u16 = consumer::MUTE.0;
+    pub const VOLUME_UP: u16 = consumer::VOLUME_UP.0;
+    pub const VOLUME_DOWN: u16 = consumer::VOLUME_DOWN.0;
+
+    pub fn to_bytes(&self) -> [u8; 2] {
+        self.usage.to_le_bytes()
+    }
+}
+
+/// Rotary dial with a push button, in the style of the Surface Dial.
+///
+/// The input report is 3 bytes: the button state in bit 0 of the first byte (the other bits are
+/// padding), then the relative rotation as a signed 16-bit little-endian Dial value, see
+/// [`DialReport`].
+pub fn dial() -> Vec<u8> {
+    vec![
+        0x05, 0x01,        // Usage Page (Generic Desktop)        0
+        0x09, 0x0e,        // Usage (System Multi-Axis Controller) 2
+        0xa1, 0x01,        // Collection (Application)            4
+        0x05, 0x0d,        // .Usage Page (Digitizers)            6
+        0x09, 0x21,        // .Usage (Puck)                       8
+        0xa1, 0x00,        // .Collection (Physical)              10
+        0x05, 0x09,        // ..Usage Page (Button)               12
+        0x09, 0x01,        // ..Usage (Button 1)                  14
+        0x15, 0x00,        // ..Logical Minimum (0)               16
+        0x25, 0x01,        // ..Logical Maximum (1)               18
+        0x75, 0x01,        // ..Report Size (1)                   20
+        0x95, 0x01,        // ..Report Count (1)                  22
+        0x81, 0x02,        // ..Input (Data,Var,Abs)              24
+        0x75, 0x07,        // ..Report Size (7)                   26
+        0x81, 0x01,        // ..Input (Cnst,Arr,Abs)              28
+        0x05, 0x01,        // ..Usage Page (Generic Desktop)      30
+        0x09, 0x37,        // ..Usage (Dial)                      32
+        0x16, 0x01, 0x80,  // ..Logical Minimum (-32767)          34
+        0x26, 0xff, 0x7f,  // ..Logical Maximum (32767)           37
+        0x75, 0x10,        // ..Report Size (16)                  40
+        0x81, 0x06,        // ..Input (Data,Var,Rel)              42
+        0xc0,              // .End Collection                     44
+        0xc0,              // End Collection                      45
+    ]
+}
+
+/// Input report of the [`dial`] device.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DialReport {
+    /// Rotation since the last report, clockwise is positive.
+    pub rotation: i16,
+    pub pressed: bool,
+}
+
+impl DialReport {
+    pub fn to_bytes(&self) -> [u8; 3] {
+        let rotation = self.rotation.to_le_bytes();
+        [self.pressed as u8, rotation[0], rotation[1]]
+    }
+}
+
+/// Joystick with four axes, a throttle, an 8-way hat switch and 32 buttons.
+///
+/// The input report is 15 bytes: the X, Y, Z and Rx axes followed by the throttle, each a signed
+/// 16-bit little-endian value in the -32767..=32767 range, then the hat switch in the low nibble
+/// of a byte (0 to 7 clockwise from up, 8 when centered) and a 32-bit bitmap of buttons 1 to 32.
+/// See [`JoystickReport`].
+pub fn joystick() -> Vec<u8> {
+    vec![
+        0x05, 0x01,        // Usage Page (Generic Desktop)        0
+        0x09, 0x04,        // Usage (Joystick)                    2
+        0xa1, 0x01,        // Collection (Application)            4
+        0x09, 0x01,        // .Usage (Pointer)                    6
+        0xa1, 0x00,        // .Collection (Physical)              8
+        0x09, 0x30,        // ..Usage (X)                         10
+        0x09, 0x31,        // ..Usage (Y)                         12
+        0x09, 0x32,        // ..Usage (Z)                         14
+        0x09, 0x33,        // ..Usage (Rx)                        16
+        0x16, 0x01, 0x80,  // ..Logical Minimum (-32767)          18
+        0x26, 0xff, 0x7f,  // ..Logical Maximum (32767)           21
+        0x75, 0x10,        // ..Report Size (16)                  24
+        0x95, 0x04,        // ..Report Count (4)                  26
+        0x81, 0x02,        // ..Input (Data,Var,Abs)              28
+        0xc0,              // .End Collection                     30
+        0x05, 0x02,        // .Usage Page (Simulation Controls)   31
+        0x09, 0xbb,        // .Usage (Throttle)                   33
+        0x95, 0x01,        // .Report Count (1)                   35
+        0x81, 0x02,        // .Input (Data,Var,Abs)               37
+        0x05, 0x01,        // .Usage Page (Generic Desktop)       39
+        0x09, 0x39,        // .Usage (Hat switch)                 41
+        0x15, 0x00,        // .Logical Minimum (0)                43
+        0x25, 0x07,        // .Logical Maximum (7)                45
+        0x35, 0x00,        // .Physical Minimum (0)               47
+        0x46, 0x3b, 0x01,  // .Physical Maximum (315)             49
+        0x65, 0x14,        // .Unit (Degrees)                     52
+        0x75, 0x04,        // .Report Size (4)                    54
+        0x81, 0x42,        // .Input (Data,Var,Abs,Null)          56
+        0x65, 0x00,        // .Unit (None)                        58
+        0x81, 0x01,        // .Input (Cnst,Arr,Abs)               60
+        0x05, 0x09,        // .Usage Page (Button)                62
+        0x19, 0x01,        // .Usage Minimum (1)                  64
+        0x29, 0x20,        // .Usage Maximum (32)                 66
+        0x25, 0x01,        // .Logical Maximum (1)                68
+        0x75, 0x01,        // .Report Size (1)                    70
+        0x95, 0x20,        // .Report Count (32)                  72
+        0x81, 0x02,        // .Input (Data,Var,Abs)               74
+        0xc0,              // End Collection                      76
+    ]
+}
+
+/// Position of a hat switch, clockwise from up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HatDirection {
+    Up = 0,
+    UpRight = 1,
+    Right = 2,
+    DownRight = 3,
+    Down = 4,
+    DownLeft = 5,
+    Left = 6,
+    UpLeft = 7,
+}
+
+/// Input report of the [`joystick`] device.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct JoystickReport {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+    pub rx: i16,
+    pub throttle: i16,
+    /// Hat switch position, `None` when centered.
+    pub hat: Option<HatDirection>,
+    /// Bitmap of the pressed buttons, bit 0 is button 1.
+    pub buttons: u32,
+}
+
+impl JoystickReport {
+    /// Value of the hat switch field when centered, outside its logical range.
+    const HAT_NULL: u8 = 8;
+
+    pub fn to_bytes(&self) -> [u8; 15] {
+        let mut report = [0; 15];
+        for (i, axis) in [self.x, self.y, self.z, self.rx, self.throttle].iter().enumerate() {
+            report[i * 2..i * 2 + 2].copy_from_slice(&axis.to_le_bytes());
+        }
+        report[10] = match self.hat {
+            Some(direction) => direction as u8,
+            None => Self::HAT_NULL,
+        };
+        report[11..].copy_from_slice(&self.buttons.to_le_bytes());
+        report
+    }
+}
+
+/// Gamepad with two sticks, 16 buttons and two rumble motors.
+///
+/// The input report is 6 bytes: the X and Y axes of the left stick and the Rx and Ry axes of the
+/// right one as signed bytes in the -127..=127 range, then a 16-bit little-endian bitmap of
+/// buttons 1 to 16. The 2-byte output report sets the strong and weak motor, see
+/// [`RumbleCommand`].
+///
+/// The motors use vendor-defined usages, so the kernel's generic HID driver doesn't offer force
+/// feedback for them. Rumble commands come from software writing the output report through
+/// hidraw, such as SDL's HIDAPI gamepad drivers.
+pub fn gamepad_rumble() -> Vec<u8> {
+    vec![
+        0x05, 0x01,        // Usage Page (Generic Desktop)       0
+        0x09, 0x05,        // Usage (Gamepad)                    2
+        0xa1, 0x01,        // Collection (Application)           4
+        0x09, 0x30,        // .Usage (X)                         6
+        0x09, 0x31,        // .Usage (Y)                         8
+        0x09, 0x33,        // .Usage (Rx)                        10
+        0x09, 0x34,        // .Usage (Ry)                        12
+        0x15, 0x81,        // .Logical Minimum (-127)            14
+        0x25, 0x7f,        // .Logical Maximum (127)             16
+        0x75, 0x08,        // .Report Size (8)                   18
+        0x95, 0x04,        // .Report Count (4)                  20
+        0x81, 0x02,        // .Input (Data,Var,Abs)              22
+        0x05, 0x09,        // .Usage Page (Button)               24
+        0x19, 0x01,        // .Usage Minimum (1)                 26
+        0x29, 0x10,        // .Usage Maximum (16)                28
+        0x15, 0x00,        // .Logical Minimum (0)               30
+        0x25, 0x01,        // .Logical Maximum (1)               32
+        0x75, 0x01,        // .Report Size (1)                   34
+        0x95, 0x10,        // .Report Count (16)                 36
+        0x81, 0x02,        // .Input (Data,Var,Abs)              38
+        0x06, 0x00, 0xff,  // .Usage Page (Vendor 0xff00)        40
+        0x09, 0x01,        // .Usage (Vendor Usage 1)            43
+        0x09, 0x02,        // .Usage (Vendor Usage 2)            45
+        0x26, 0xff, 0x00,  // .Logical Maximum (255)             47
+        0x75, 0x08,        // .Report Size (8)                   50
+        0x95, 0x02,        // .Report Count (2)                  52
+        0x91, 0x02,        // .Output (Data,Var,Abs)             54
+        0xc0,              // End Collection                     56
+    ]
+}
+
+/// Output report of the [`gamepad_rumble`] device, the intensity of each motor, 0 when off.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RumbleCommand {
+    /// Low-frequency motor, usually on the left.
+    pub strong: u8,
+    /// High-frequency motor, usually on the right.
+    pub weak: u8,
+}
+
+impl RumbleCommand {
+    /// Decodes an output report, `None` if it has the wrong length.
+    pub fn from_bytes(report: &[u8]) -> Option<Self> {
+        match *report {
+            [strong, weak] => Some(RumbleCommand { strong, weak }),
+            _ => None,
+        }
+    }
+}
+
+/// Pen (stylus) of a graphics tablet, with pressure and tilt.
+///
+/// The input report is 9 bytes: the Tip Switch, Barrel Switch and In Range bits in the low bits
+/// of the first byte, the X and Y position as 16-bit little-endian values in the 0..=`width` and
+/// 0..=`height` ranges, the tip pressure as a 16-bit little-endian value in the
+/// 0..=[`PenReport::MAX_PRESSURE`] range, then the X and Y tilt as signed bytes in degrees,
+/// within -[`PenReport::MAX_TILT`]..=[`PenReport::MAX_TILT`]. See [`PenReport`].
+///
+/// A position unit is 10 µm, so a 200 mm wide tablet has a `width` of 20000. The physical size
+/// matters: desktop environments ignore tablets without a resolution.
+pub fn pen(width: u16, height: u16) -> Vec<u8> {
+    let (w, h) = (width.to_le_bytes(), height.to_le_bytes());
+    vec![
+        0x05, 0x0d,                    // Usage Page (Digitizers)            0
+        0x09, 0x02,                    // Usage (Pen)                        2
+        0xa1, 0x01,                    // Collection (Application)           4
+        0x09, 0x20,                    // .Usage (Stylus)                    6
+        0xa1, 0x00,                    // .Collection (Physical)             8
+        0x09, 0x42,                    // ..Usage (Tip Switch)               10
+        0x09, 0x44,                    // ..Usage (Barrel Switch)            12
+        0x09, 0x32,                    // ..Usage (In Range)                 14
+        0x15, 0x00,                    // ..Logical Minimum (0)              16
+        0x25, 0x01,                    // ..Logical Maximum (1)              18
+        0x75, 0x01,                    // ..Report Size (1)                  20
+        0x95, 0x03,                    // ..Report Count (3)                 22
+        0x81, 0x02,                    // ..Input (Data,Var,Abs)             24
+        0x95, 0x05,                    // ..Report Count (5)                 26
+        0x81, 0x01,                    // ..Input (Cnst,Arr,Abs)             28
+        0x05, 0x01,                    // ..Usage Page (Generic Desktop)     30
+        0x09, 0x30,                    // ..Usage (X)                        32
+        0x27, w[0], w[1], 0x00, 0x00,  // ..Logical Maximum (width)          34
+        0x47, w[0], w[1], 0x00, 0x00,  // ..Physical Maximum (width)         39
+        0x65, 0x11,                    // ..Unit (SI Linear: cm)             44
+        0x55, 0x0d,                    // ..Unit Exponent (-3)               46
+        0x75, 0x10,                    // ..Report Size (16)                 48
+        0x95, 0x01,                    // ..Report Count (1)                 50
+        0x81, 0x02,                    // ..Input (Data,Var,Abs)             52
+        0x09, 0x31,                    // ..Usage (Y)                        54
+        0x27, h[0], h[1], 0x00, 0x00,  // ..Logical Maximum (height)         56
+        0x47, h[0], h[1], 0x00, 0x00,  // ..Physical Maximum (height)        61
+        0x81, 0x02,                    // ..Input (Data,Var,Abs)             66
+        0x05, 0x0d,                    // ..Usage Page (Digitizers)          68
+        0x09, 0x30,                    // ..Usage (Tip Pressure)             70
+        0x26, 0xff, 0x0f,              // ..Logical Maximum (4095)           72
+        0x45, 0x00,                    // ..Physical Maximum (0)             75
+        0x65, 0x00,                    // ..Unit (None)                      77
+        0x55, 0x00,                    // ..Unit Exponent (0)                79
+        0x81, 0x02,                    // ..Input (Data,Var,Abs)             81
+        0x09, 0x3d,                    // ..Usage (X Tilt)                   83
+        0x09, 0x3e,                    // ..Usage (Y Tilt)                   85
+        0x15, 0xa6,                    // ..Logical Minimum (-90)            87
+        0x25, 0x5a,                    // ..Logical Maximum (90)             89
+        0x35, 0xa6,                    // ..Physical Minimum (-90)           91
+        0x45, 0x5a,                    // ..Physical Maximum (90)            93
+        0x65, 0x14,                    // ..Unit (English Rotation: deg)     95
+        0x75, 0x08,                    // ..Report Size (8)                  97
+        0x95, 0x02,                    // ..Report Count (2)                 99
+        0x81, 0x02,                    // ..Input (Data,Var,Abs)             101
+        0xc0,                          // .End Collection                    103
+        0xc0,                          // End Collection                     104
+    ]
+}
+
+/// Input report of the [`pen`] device.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PenReport {
+    pub x: u16,
+    pub y: u16,
+    /// Tip pressure, clamped to [`MAX_PRESSURE`](Self::MAX_PRESSURE).
+    pub pressure: u16,
+    /// Tilt towards positive X in degrees, clamped to [`MAX_TILT`](Self::MAX_TILT).
+    pub tilt_x: i8,
+    /// Tilt towards positive Y in degrees, clamped to [`MAX_TILT`](Self::MAX_TILT).
+    pub tilt_y: i8,
+    /// The pen is close enough to the tablet to be tracked.
+    pub in_range: bool,
+    /// The tip touches the tablet.
+    pub touching: bool,
+    /// The barrel (side) button is pressed.
+    pub barrel: bool,
+}
+
+impl PenReport {
+    pub const MAX_PRESSURE: u16 = 4095;
+    pub const MAX_TILT: i8 = 90;
+
+    pub fn to_bytes(&self) -> [u8; 9] {
+        let (x, y) = (self.x.to_le_bytes(), self.y.to_le_bytes());
+        let pressure = self.pressure.min(Self::MAX_PRESSURE).to_le_bytes();
+        let tilt = |tilt: i8| tilt.clamp(-Self::MAX_TILT, Self::MAX_TILT) as u8;
+        let flags = self.touching as u8 | (self.barrel as u8) << 1 | (self.in_range as u8) << 2;
+        [flags, x[0], x[1], y[0], y[1], pressure[0], pressure[1], tilt(self.tilt_x), tilt(self.tilt_y)]
+    }
+}
+
+/// Touchscreen reporting up to `max_contacts` contacts at once, in the layout Windows expects and
+/// the kernel's hid-multitouch driver handles.
+///
+/// The input report has one 6-byte slot per contact: the Tip Switch in bit 0 of the first byte,
+/// the contact ID, then the X and Y position as 16-bit little-endian values in the 0..=`width`
+/// and 0..=`height` ranges. The slots are followed by the scan time, a 16-bit little-endian
+/// value in units of 100 µs that wraps around, and the number of valid slots as a byte. See
+/// [`MultitouchReport`].
+///
+/// Like [`pen`], a position unit is 10 µm. The descriptor has no Contact Count Maximum feature
+/// report, which would have to be answered on every GetReport, so the kernel falls back to its
+/// own default for the number of contacts.
+///
+/// Every contact adds 76 bytes to the descriptor, so at most [`MultitouchReport::MAX_CONTACTS`]
+/// fit in [`MAX_DESCRIPTOR_SIZE`](crate::MAX_DESCRIPTOR_SIZE).
+///
+/// # Panics
+///
+/// Panics if `max_contacts` is 0 or more than [`MultitouchReport::MAX_CONTACTS`].
+pub fn multitouch(max_contacts: u8, width: u16, height: u16) -> Vec<u8> {
+    assert!(max_contacts > 0, "a touchscreen needs at least one contact");
+    assert!(
+        max_contacts <= MultitouchReport::MAX_CONTACTS,
+        "the descriptor only fits {} contacts",
+        MultitouchReport::MAX_CONTACTS
+    );
+    let (w, h) = (width.to_le_bytes(), height.to_le_bytes());
+    let mut rdesc = vec![
+        0x05, 0x0d,  // Usage Page (Digitizers)
+        0x09, 0x04,  // Usage (Touch Screen)
+        0xa1, 0x01,  // Collection (Application)
+    ];
+    for _ in 0..max_contacts {
+        rdesc.extend_from_slice(&[
+            0x09, 0x22,                    // .Usage (Finger)
+            0xa1, 0x02,                    // .Collection (Logical)
+            0x05, 0x0d,                    // ..Usage Page (Digitizers)
+            0x09, 0x42,                    // ..Usage (Tip Switch)
+            0x15, 0x00,                    // ..Logical Minimum (0)
+            0x25, 0x01,                    // ..Logical Maximum (1)
+            0x75, 0x01,                    // ..Report Size (1)
+            0x95, 0x01,                    // ..Report Count (1)
+            0x81, 0x02,                    // ..Input (Data,Var,Abs)
+            0x95, 0x07,                    // ..Report Count (7)
+            0x81, 0x01,                    // ..Input (Cnst,Arr,Abs)
+            0x09, 0x51,                    // ..Usage (Contact Identifier)
+            0x26, 0xff, 0x00,              // ..Logical Maximum (255)
+            0x75, 0x08,                    // ..Report Size (8)
+            0x95, 0x01,                    // ..Report Count (1)
+            0x81, 0x02,                    // ..Input (Data,Var,Abs)
+            0x05, 0x01,                    // ..Usage Page (Generic Desktop)
+            0x09, 0x30,                    // ..Usage (X)
+            0x27, w[0], w[1], 0x00, 0x00,  // ..Logical Maximum (width)
+            0x47, w[0], w[1], 0x00, 0x00,  // ..Physical Maximum (width)
+            0x65, 0x11,                    // ..Unit (SI Linear: cm)
+            0x55, 0x0d,                    // ..Unit Exponent (-3)
+            0x75, 0x10,                    // ..Report Size (16)
+            0x81, 0x02,                    // ..Input (Data,Var,Abs)
+            0x09, 0x31,                    // ..Usage (Y)
+            0x27, h[0], h[1], 0x00, 0x00,  // ..Logical Maximum (height)
+            0x47, h[0], h[1], 0x00, 0x00,  // ..Physical Maximum (height)
+            0x81, 0x02,                    // ..Input (Data,Var,Abs)
+            0x45, 0x00,                    // ..Physical Maximum (0)
+            0x65, 0x00,                    // ..Unit (None)
+            0x55, 0x00,                    // ..Unit Exponent (0)
+            0xc0,                          // .End Collection
+        ]);
+    }
+    rdesc.extend_from_slice(&[
+        0x05, 0x0d,                    // .Usage Page (Digitizers)
+        0x09, 0x56,                    // .Usage (Scan Time)
+        0x27, 0xff, 0xff, 0x00, 0x00,  // .Logical Maximum (65535)
+        0x47, 0xff, 0xff, 0x00, 0x00,  // .Physical Maximum (65535)
+        0x66, 0x01, 0x10,              // .Unit (SI Linear: s)
+        0x55, 0x0c,                    // .Unit Exponent (-4)
+        0x75, 0x10,                    // .Report Size (16)
+        0x95, 0x01,                    // .Report Count (1)
+        0x81, 0x02,                    // .Input (Data,Var,Abs)
+        0x45, 0x00,                    // .Physical Maximum (0)
+        0x65, 0x00,                    // .Unit (None)
+        0x55, 0x00,                    // .Unit Exponent (0)
+        0x09, 0x54,                    // .Usage (Contact Count)
+        0x26, max_contacts, 0x00,      // .Logical Maximum (max_contacts)
+        0x75, 0x08,                    // .Report Size (8)
+        0x81, 0x02,                    // .Input (Data,Var,Abs)
+        0xc0,                          // End Collection
+    ]);
+    rdesc
+}
+
+/// Contact of a [`MultitouchReport`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Contact {
+    /// Identifies the contact from the moment it touches until it is lifted.
+    pub id: u8,
+    pub x: u16,
+    pub y: u16,
+    /// Whether the contact touches the screen. A lifted contact has to be reported once with this
+    /// unset for the host to end it.
+    pub touching: bool,
+}
+
+/// Input report of the [`multitouch`] device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MultitouchReport {
+    pub contacts: Vec<Contact>,
+    /// Time of the scan in units of 100 µs, wrapping around. Only differences between reports
+    /// matter.
+    pub scan_time: u16,
+}
+
+impl MultitouchReport {
+    /// Most contacts a [`multitouch`] descriptor can declare.
+    pub const MAX_CONTACTS: u8 = 53;
+
+    const CONTACT_SIZE: usize = 6;
+
+    /// Encodes the report for a [`multitouch`] device with `max_contacts` contacts.
+    ///
+    /// Contacts beyond `max_contacts` are left out, unused slots are zeroed.
+    pub fn to_bytes(&self, max_contacts: u8) -> Vec<u8> {
+        let count = self.contacts.len().min(max_contacts as usize);
+        let mut report = vec![0; max_contacts as usize * Self::CONTACT_SIZE + 3];
+        for (slot, contact) in report.chunks_exact_mut(Self::CONTACT_SIZE).zip(&self.contacts[..count]) {
+            let (x, y) = (contact.x.to_le_bytes(), contact.y.to_le_bytes());
+            slot.copy_from_slice(&[contact.touching as u8, contact.id, x[0], x[1], y[0], y[1]]);
+        }
+        let len = report.len();
+        report[len - 3..len - 1].copy_from_slice(&self.scan_time.to_le_bytes());
+        report[len - 1] = count as u8;
+        report
+    }
+}
+
+/// Battery Strength feature report, to add inside the application collection of a device.
+///
+/// The kernel reads the battery level of a device with this report through GetReport, and
+/// exposes it as a power supply. The feature report is 2 bytes: `report_id` followed by the
+/// level in percent, see [`Device::set_battery_level`](crate::Device::set_battery_level). The
+/// kernel expects the report ID in the reply, so the rest of the descriptor has to use report
+/// IDs too. This changes the usage page and the logical range, items following it have to set
+/// their own.
+pub fn battery(report_id: u8) -> Vec<u8> {
+    vec![
+        0x85, report_id,  // Report ID (report_id)                0
+        0x05, 0x06,       // Usage Page (Generic Device Controls) 2
+        0x09, 0x20,       // Usage (Battery Strength)             4
+        0x15, 0x00,       // Logical Minimum (0)                  6
+        0x25, 0x64,       // Logical Maximum (100)                8
+        0x75, 0x08,       // Report Size (8)                      10
+        0x95, 0x01,       // Report Count (1)                     12
+        0xb1, 0x02,       // Feature (Data,Var,Abs)               14
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::parse_descriptor;
+    use crate::usages::generic_desktop;
+    use crate::ReportType;
+
+    #[test]
+    fn boot_report_lengths() {
+        let info = parse_descriptor(&boot_keyboard()).unwrap();
+        assert_eq!(info.report(None, ReportType::Input).unwrap().byte_length(), 8);
+        assert_eq!(info.report(None, ReportType::Output).unwrap().byte_length(), 1);
+
+        let info = parse_descriptor(&boot_mouse()).unwrap();
+        assert_eq!(info.report(None, ReportType::Input).unwrap().byte_length(), 3);
+    }
+
+    #[test]
+    fn battery_report() {
+        let mut rdesc = vec![0x05, 0x01, 0x09, 0x02, 0xa1, 0x01];
+        rdesc.extend(battery(4));
+        rdesc.push(0xc0);
+        let info = parse_descriptor(&rdesc).unwrap();
+        assert_eq!(info.report(Some(4), ReportType::Feature).unwrap().byte_length(), 2);
+    }
+
+    #[test]
+    fn keyboard_report() {
+        let report = KeyboardReport { modifiers: 0x02, keys: [0x04, 0, 0, 0, 0, 0] };
+        let boot = [0x02, 0x00, 0x04, 0, 0, 0, 0, 0];
+        assert_eq!(report.to_bytes(Protocol::Boot, None), boot);
+        assert_eq!(report.to_bytes(Protocol::Boot, Some(3)), boot);
+        assert_eq!(report.to_bytes(Protocol::Report, None), boot);
+        assert_eq!(report.to_bytes(Protocol::Report, Some(3)), [0x03, 0x02, 0x00, 0x04, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn hires_mouse() {
+        let rdesc = mouse_hires();
+        let info = parse_descriptor(&rdesc).unwrap();
+        assert_eq!(info.report(None, ReportType::Input).unwrap().byte_length(), 5);
+        assert_eq!(info.report(None, ReportType::Feature).unwrap().byte_length(), 1);
+        /* the multiplier has to share a logical collection with the wheel */
+        assert_eq!(rdesc[48..52], [0xa1, 0x02, 0x09, generic_desktop::RESOLUTION_MULTIPLIER.0 as u8]);
+        assert_eq!(rdesc[70..72], [0x09, generic_desktop::WHEEL.0 as u8]);
+    }
+
+    #[test]
+    fn mouse_report() {
+        let report = MouseReport { buttons: 0xff, dx: -300, dy: 5 };
+        assert_eq!(report.to_bytes(), [0x07, 0x81, 0x05]);
+        let report = MouseReport { buttons: 0x01, dx: 127, dy: 1000 };
+        assert_eq!(report.to_bytes(), [0x01, 0x7f, 0x7f]);
+    }
+
+    #[test]
+    fn dial_report() {
+        let info = parse_descriptor(&dial()).unwrap();
+        assert_eq!(info.report(None, ReportType::Input).unwrap().byte_length(), 3);
+
+        let report = DialReport { rotation: -2, pressed: true };
+        assert_eq!(report.to_bytes(), [0x01, 0xfe, 0xff]);
+        let report = DialReport { rotation: 0x123, pressed: false };
+        assert_eq!(report.to_bytes(), [0x00, 0x23, 0x01]);
+    }
+
+    #[test]
+    fn consumer_report() {
+        let report = ConsumerReport { usage: ConsumerReport::VOLUME_UP };
+        assert_eq!(report.to_bytes(), [0xe9, 0x00]);
+        assert_eq!(ConsumerReport::default().to_bytes(), [0x00, 0x00]);
+    }
+
+    #[test]
+    fn joystick_report() {
+        let info = parse_descriptor(&joystick()).unwrap();
+        assert_eq!(info.report(None, ReportType::Input).unwrap().byte_length(), 15);
+
+        let report = JoystickReport {
+            x: -1,
+            y: 0x0102,
+            rx: i16::MAX,
+            throttle: -32767,
+            buttons: 1 << 31 | 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            report.to_bytes(),
+            [0xff, 0xff, 0x02, 0x01, 0x00, 0x00, 0xff, 0x7f, 0x01, 0x80, 0x08, 0x01, 0x00, 0x00, 0x80]
+        );
+    }
+
+    #[test]
+    fn gamepad_reports() {
+        let info = parse_descriptor(&gamepad_rumble()).unwrap();
+        assert_eq!(info.report(None, ReportType::Input).unwrap().byte_length(), 6);
+        assert_eq!(info.report(None, ReportType::Output).unwrap().byte_length(), 2);
+
+        assert_eq!(RumbleCommand::from_bytes(&[0xff, 0x40]), Some(RumbleCommand { strong: 0xff, weak: 0x40 }));
+        assert_eq!(RumbleCommand::from_bytes(&[0xff]), None);
+    }
+
+    #[test]
+    fn pen_report() {
+        let info = parse_descriptor(&pen(20000, 15000)).unwrap();
+        assert_eq!(info.report(None, ReportType::Input).unwrap().byte_length(), 9);
+
+        let report = PenReport {
+            x: 10000,
+            y: 7500,
+            pressure: 2048,
+            tilt_x: -45,
+            tilt_y: 30,
+            in_range: true,
+            touching: true,
+            barrel: false,
+        };
+        assert_eq!(report.to_bytes(), [0x05, 0x10, 0x27, 0x4c, 0x1d, 0x00, 0x08, 0xd3, 0x1e]);
+
+        let report =
+            PenReport { pressure: u16::MAX, tilt_x: i8::MIN, tilt_y: i8::MAX, barrel: true, ..Default::default() };
+        assert_eq!(report.to_bytes(), [0x02, 0, 0, 0, 0, 0xff, 0x0f, 0xa6, 0x5a]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the descriptor only fits 53 contacts")]
+    fn multitouch_too_many_contacts() {
+        multitouch(54, 30000, 20000);
+    }
+
+    #[test]
+    fn multitouch_report() {
+        let info = parse_descriptor(&multitouch(2, 30000, 20000)).unwrap();
+        assert_eq!(info.report(None, ReportType::Input).unwrap().byte_length(), 15);
+        assert_eq!(parse_descriptor(&multitouch(10, 30000, 20000)).unwrap().reports[0].byte_length(), 63);
+        assert!(multitouch(MultitouchReport::MAX_CONTACTS, 30000, 20000).len() <= crate::MAX_DESCRIPTOR_SIZE);
+        assert!(multitouch(MultitouchReport::MAX_CONTACTS, 30000, 20000).len() + 76 > crate::MAX_DESCRIPTOR_SIZE);
+
+        let report = MultitouchReport {
+            contacts: vec![
+                Contact { id: 3, x: 0x1234, y: 0x0100, touching: true },
+                Contact { id: 4, x: 30000, y: 20000, touching: false },
+            ],
+            scan_time: 0xbeef,
+        };
+        assert_eq!(
+            report.to_bytes(2),
+            [0x01, 0x03, 0x34, 0x12, 0x00, 0x01, 0x00, 0x04, 0x30, 0x75, 0x20, 0x4e, 0xef, 0xbe, 0x02]
+        );
+        assert_eq!(report.to_bytes(1), [0x01, 0x03, 0x34, 0x12, 0x00, 0x01, 0xef, 0xbe, 0x01]);
+        assert_eq!(report.to_bytes(3)[12..], [0, 0, 0, 0, 0, 0, 0xef, 0xbe, 0x02]);
+    }
+
+    #[test]
+    fn hat_encoding() {
+        let directions = [
+            (Some(HatDirection::Up), 0),
+            (Some(HatDirection::UpRight), 1),
+            (Some(HatDirection::Right), 2),
+            (Some(HatDirection::DownRight), 3),
+            (Some(HatDirection::Down), 4),
+            (Some(HatDirection::DownLeft), 5),
+            (Some(HatDirection::Left), 6),
+            (Some(HatDirection::UpLeft), 7),
+            (None, 8),
+        ];
+        for &(hat, value) in &directions {
+            assert_eq!(JoystickReport { hat, ..Default::default() }.to_bytes()[10], value);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: MIT
+
+//! Helpers for encoding report data.
+
+use crate::UhidError;
+
+/// Builds a fixed-length report, refusing writes past its end.
+///
+/// Fields are packed the way HID expects them: starting from the least significant bit of the
+/// first byte, with multi-byte fields in little-endian order.
+#[derive(Clone, Debug)]
+pub struct ReportBuilder {
+    data: Vec<u8>,
+    cursor: usize,
+}
+
+impl ReportBuilder {
+    /// Starts a zero-filled report of `len` bytes.
+    pub fn new(len: usize) -> Self {
+        ReportBuilder {
+            data: vec![0; len],
+            cursor: 0,
+        }
+    }
+
+    /// Writes the low `len` bits of `value` starting at bit `start_bit`.
+    pub fn set_bits(&mut self, start_bit: usize, len: usize, value: u32) -> Result<&mut Self, UhidError> {
+        let end = start_bit.saturating_add(len);
+        if len > 32 || end > self.data.len() * 8 {
+            return Err(UhidError::ReportOverflow { bit: end, len: self.data.len() });
+        }
+
+        for i in 0..len {
+            let bit = start_bit + i;
+            let mask = 1 << (bit % 8);
+            if value >> i & 1 == 1 {
+                self.data[bit / 8] |= mask;
+            } else {
+                self.data[bit / 8] &= !mask;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Writes `value` at the next byte, starting from the beginning of the report.
+    ///
+    /// The position is independent of [`set_bits`](Self::set_bits).
+    pub fn push_byte(&mut self, value: u8) -> Result<&mut Self, UhidError> {
+        match self.data.get_mut(self.cursor) {
+            Some(byte) => *byte = value,
+            None => return Err(UhidError::ReportOverflow { bit: (self.cursor + 1) * 8, len: self.data.len() }),
+        }
+        self.cursor += 1;
+        Ok(self)
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+}
+
+/// Encodes `value` as a signed byte, clamped to -127..=127.
+///
+/// The range is symmetric like the logical ranges relative axes are normally declared with,
+/// -128 would be outside of them.
+pub fn encode_i8_clamped(value: i32) -> u8 {
+    value.clamp(-127, 127) as i8 as u8
+}
+
+/// Encodes `value` as a signed 16-bit little-endian value, clamped to -32767..=32767.
+pub fn encode_i16_clamped(value: i32) -> [u8; 2] {
+    (value.clamp(-32767, 32767) as i16).to_le_bytes()
+}
+
+/// Like [`encode_i8_clamped`], but fails if `value` is out of range.
+pub fn encode_i8_checked(value: i32) -> Result<u8, UhidError> {
+    if !(-127..=127).contains(&value) {
+        return Err(UhidError::ValueOutOfRange { value: value as i64, min: -127, max: 127 });
+    }
+    Ok(value as i8 as u8)
+}
+
+/// Like [`encode_i16_clamped`], but fails if `value` is out of range.
+pub fn encode_i16_checked(value: i32) -> Result<[u8; 2], UhidError> {
+    if !(-32767..=32767).contains(&value) {
+        return Err(UhidError::ValueOutOfRange { value: value as i64, min: -32767, max: 32767 });
+    }
+    Ok((value as i16).to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits() {
+        let mut report = ReportBuilder::new(3);
+        report.set_bits(0, 1, 1).unwrap().set_bits(2, 1, 1).unwrap();
+        report.set_bits(6, 4, 0b1011).unwrap();
+        report.set_bits(12, 12, 0xabc).unwrap();
+        assert_eq!(report.build(), [0b1100_0101, 0xc2, 0xab]);
+
+        report.set_bits(6, 4, 0).unwrap();
+        assert_eq!(report.build(), [0b0000_0101, 0xc0, 0xab]);
+    }
+
+    #[test]
+    fn signed() {
+        let mut report = ReportBuilder::new(2);
+        report.set_bits(4, 8, -2i8 as u8 as u32).unwrap();
+        assert_eq!(report.build(), [0xe0, 0x0f]);
+    }
+
+    #[test]
+    fn bytes() {
+        let mut report = ReportBuilder::new(2);
+        report.push_byte(0x01).unwrap().push_byte(0x02).unwrap();
+        assert_eq!(report.build(), [0x01, 0x02]);
+        assert!(report.push_byte(0x03).is_err());
+    }
+
+    #[test]
+    fn overflow() {
+        let mut report = ReportBuilder::new(2);
+        assert!(report.set_bits(0, 16, 0xffff).is_ok());
+        assert!(report.set_bits(9, 8, 0xff).is_err());
+        assert!(report.set_bits(0, 33, 0).is_err());
+        assert!(report.set_bits(usize::MAX, 2, 0).is_err());
+    }
+
+    #[test]
+    fn clamped() {
+        assert_eq!(encode_i8_clamped(0), 0x00);
+        assert_eq!(encode_i8_clamped(-1), 0xff);
+        assert_eq!(encode_i8_clamped(127), 0x7f);
+        assert_eq!(encode_i8_clamped(128), 0x7f);
+        assert_eq!(encode_i8_clamped(-127), 0x81);
+        assert_eq!(encode_i8_clamped(-128), 0x81);
+        assert_eq!(encode_i8_clamped(i32::MIN), 0x81);
+
+        assert_eq!(encode_i16_clamped(-2), [0xfe, 0xff]);
+        assert_eq!(encode_i16_clamped(40_000), [0xff, 0x7f]);
+        assert_eq!(encode_i16_clamped(-40_000), [0x01, 0x80]);
+    }
+
+    #[test]
+    fn checked() {
+        assert_eq!(encode_i8_checked(-127).unwrap(), 0x81);
+        match encode_i8_checked(-128) {
+            Err(UhidError::ValueOutOfRange { value: -128, min: -127, max: 127 }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(encode_i16_checked(32767).unwrap(), [0xff, 0x7f]);
+        assert!(encode_i16_checked(32768).is_err());
+    }
+}
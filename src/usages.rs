@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: MIT
+
+//! Subset of the HID usage tables, covering the devices in [`presets`](crate::presets) apart from
+//! their vendor-defined usages.
+//!
+//! These can be passed to the [`ReportDescriptor`](crate::descriptor::ReportDescriptor) builder
+//! instead of bare numbers.
+
+/// Usage page, the high 16 bits of a usage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsagePage(pub u16);
+
+impl UsagePage {
+    pub const GENERIC_DESKTOP: Self = UsagePage(0x01);
+    pub const SIMULATION_CONTROLS: Self = UsagePage(0x02);
+    pub const GENERIC_DEVICE_CONTROLS: Self = UsagePage(0x06);
+    pub const KEYBOARD: Self = UsagePage(0x07);
+    pub const LEDS: Self = UsagePage(0x08);
+    pub const BUTTON: Self = UsagePage(0x09);
+    pub const CONSUMER: Self = UsagePage(0x0c);
+    pub const DIGITIZERS: Self = UsagePage(0x0d);
+}
+
+impl From<UsagePage> for u16 {
+    fn from(page: UsagePage) -> u16 {
+        page.0
+    }
+}
+
+/// Usage ID within a usage page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Usage(pub u16);
+
+impl From<Usage> for u16 {
+    fn from(usage: Usage) -> u16 {
+        usage.0
+    }
+}
+
+/// Usages of the [`UsagePage::GENERIC_DESKTOP`] page.
+pub mod generic_desktop {
+    use super::Usage;
+
+    pub const POINTER: Usage = Usage(0x01);
+    pub const MOUSE: Usage = Usage(0x02);
+    pub const JOYSTICK: Usage = Usage(0x04);
+    pub const GAMEPAD: Usage = Usage(0x05);
+    pub const KEYBOARD: Usage = Usage(0x06);
+    pub const KEYPAD: Usage = Usage(0x07);
+    pub const SYSTEM_MULTI_AXIS_CONTROLLER: Usage = Usage(0x0e);
+    pub const X: Usage = Usage(0x30);
+    pub const Y: Usage = Usage(0x31);
+    pub const Z: Usage = Usage(0x32);
+    pub const RX: Usage = Usage(0x33);
+    pub const RY: Usage = Usage(0x34);
+    pub const DIAL: Usage = Usage(0x37);
+    pub const WHEEL: Usage = Usage(0x38);
+    pub const HAT_SWITCH: Usage = Usage(0x39);
+    pub const RESOLUTION_MULTIPLIER: Usage = Usage(0x48);
+}
+
+/// Usages of the [`UsagePage::SIMULATION_CONTROLS`] page.
+pub mod simulation_controls {
+    use super::Usage;
+
+    pub const THROTTLE: Usage = Usage(0xbb);
+}
+
+/// Usages of the [`UsagePage::GENERIC_DEVICE_CONTROLS`] page.
+pub mod generic_device_controls {
+    use super::Usage;
+
+    pub const BATTERY_STRENGTH: Usage = Usage(0x20);
+}
+
+/// Usages of the [`UsagePage::CONSUMER`] page.
+pub mod consumer {
+    use super::Usage;
+
+    pub const CONSUMER_CONTROL: Usage = Usage(0x01);
+    pub const PLAY_PAUSE: Usage = Usage(0xcd);
+    pub const MUTE: Usage = Usage(0xe2);
+    pub const VOLUME_UP: Usage = Usage(0xe9);
+    pub const VOLUME_DOWN: Usage = Usage(0xea);
+}
+
+/// Usages of the [`UsagePage::DIGITIZERS`] page.
+pub mod digitizers {
+    use super::Usage;
+
+    pub const PEN: Usage = Usage(0x02);
+    pub const TOUCH_SCREEN: Usage = Usage(0x04);
+    pub const STYLUS: Usage = Usage(0x20);
+    pub const PUCK: Usage = Usage(0x21);
+    pub const FINGER: Usage = Usage(0x22);
+    pub const TIP_PRESSURE: Usage = Usage(0x30);
+    pub const IN_RANGE: Usage = Usage(0x32);
+    pub const X_TILT: Usage = Usage(0x3d);
+    pub const Y_TILT: Usage = Usage(0x3e);
+    pub const TIP_SWITCH: Usage = Usage(0x42);
+    pub const BARREL_SWITCH: Usage = Usage(0x44);
+    pub const CONTACT_IDENTIFIER: Usage = Usage(0x51);
+    pub const CONTACT_COUNT: Usage = Usage(0x54);
+    pub const SCAN_TIME: Usage = Usage(0x56);
+}